@@ -0,0 +1,102 @@
+use crate::{message::NodeId, message_queue::TimeStamp};
+
+/// Upload and download link of every node, reset for each simulated message.
+///
+/// A node sends one message at a time on its upload link, so sending to `t` peers back to back
+/// queues behind each other. The receiver's download link is shared by everything sent to it.
+/// Bandwidths are in kbit/s (= bit/ms), `0` means unlimited.
+#[derive(Debug, Clone)]
+pub struct Links {
+    upload_bandwidth: u32,
+    download_bandwidth: u32,
+    /// when the upload link of each node finishes its last queued send.
+    upload_free_ts: Vec<TimeStamp>,
+    /// when the download link of each node finishes its last queued receive.
+    download_free_ts: Vec<TimeStamp>,
+}
+
+impl Links {
+    pub fn new(node_size: usize, upload_bandwidth: u32, download_bandwidth: u32) -> Links {
+        Links {
+            upload_bandwidth,
+            download_bandwidth,
+            upload_free_ts: vec![0; node_size],
+            download_free_ts: vec![0; node_size],
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.upload_free_ts.iter_mut().for_each(|ts| *ts = 0);
+        self.download_free_ts.iter_mut().for_each(|ts| *ts = 0);
+    }
+
+    /// when `size` bytes sent by `from` at `ts` are fully received by `to`,
+    /// the first bit taking `propagation` ms on the way.
+    pub fn transmit(
+        &mut self,
+        from: NodeId,
+        to: NodeId,
+        ts: TimeStamp,
+        size: u32,
+        propagation: u32,
+    ) -> TimeStamp {
+        let (send_start, send_done) = self.upload(from, ts, size);
+        let recv_start = (send_start + propagation).max(self.download_free_ts[to]);
+        let recv_done = (send_done + propagation)
+            .max(recv_start + transmission_time(size, self.download_bandwidth));
+        self.download_free_ts[to] = recv_done;
+        recv_done
+    }
+
+    /// queue `size` bytes on the upload link of `from` at `ts`, return when they start and
+    /// finish going out. Alone for messages that never reach the receiver.
+    pub fn upload(&mut self, from: NodeId, ts: TimeStamp, size: u32) -> (TimeStamp, TimeStamp) {
+        let send_start = ts.max(self.upload_free_ts[from]);
+        let send_done = send_start + transmission_time(size, self.upload_bandwidth);
+        self.upload_free_ts[from] = send_done;
+        (send_start, send_done)
+    }
+}
+
+/// ms to put `size` bytes on a `bandwidth` kbit/s link, rounded up.
+pub fn transmission_time(size: u32, bandwidth: u32) -> u32 {
+    if bandwidth == 0 {
+        return 0;
+    }
+    (size as u64 * 8).div_ceil(bandwidth as u64) as u32
+}
+
+#[test]
+fn test_transmission_time() {
+    assert_eq!(transmission_time(1000, 0), 0);
+    // 1000 bytes on 8 Mbit/s
+    assert_eq!(transmission_time(1000, 8000), 1);
+    assert_eq!(transmission_time(1001, 8000), 2);
+    assert_eq!(transmission_time(125_000, 1000), 1000);
+}
+
+#[test]
+fn test_links_queue_sends() {
+    let mut unlimited = Links::new(3, 0, 0);
+    assert_eq!(unlimited.transmit(0, 1, 10, 1_000_000, 100), 110);
+    assert_eq!(unlimited.transmit(0, 2, 10, 1_000_000, 100), 110);
+
+    // 10 ms per message on both links
+    let mut links = Links::new(4, 800, 800);
+    assert_eq!(links.transmit(0, 1, 0, 1000, 100), 110);
+    // second send waits for the first one on the upload link
+    assert_eq!(links.transmit(0, 2, 0, 1000, 100), 120);
+    // node 3 sends to node 1 at the same time, its download link is busy until 110
+    assert_eq!(links.transmit(3, 1, 0, 1000, 100), 120);
+
+    links.reset();
+    assert_eq!(links.transmit(0, 1, 0, 1000, 100), 110);
+    // a lost message still holds the upload link
+    assert_eq!(links.upload(0, 0, 1000), (10, 20));
+    assert_eq!(links.transmit(0, 2, 0, 1000, 100), 130);
+
+    // slow download link is the bottleneck
+    let mut links = Links::new(2, 8000, 800);
+    assert_eq!(links.transmit(0, 1, 0, 1000, 100), 110);
+    assert_eq!(links.transmit(0, 1, 0, 1000, 100), 120);
+}
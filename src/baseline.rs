@@ -0,0 +1,160 @@
+use crate::{
+    behaviour::Behaviour,
+    latency::DelayKind,
+    message::{Message, NodeId},
+    message_queue::TimeStamp,
+    network::Network,
+    protocol::Protocol,
+};
+
+/// Send full message to every neighbour but the one it came from, once, on first receipt.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Flood;
+
+impl Protocol for Flood {
+    fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        if !first_receipt(net, &message, ts) {
+            return;
+        }
+        let dst_list: Vec<NodeId> = net
+            .topology()
+            .neighbours(message.to)
+            .into_iter()
+            .filter(|dst| *dst != message.from)
+            .collect();
+        forward(net, &message, &dst_list, ts);
+    }
+}
+
+/// Infect and die: on first receipt push full message to `t` random neighbours,
+/// unless it already took `ttl` hops.
+#[derive(Debug, Clone, Copy)]
+pub struct PushGossip {
+    pub ttl: u32,
+}
+
+impl Protocol for PushGossip {
+    fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        if !first_receipt(net, &message, ts) || message.hop_num >= self.ttl {
+            return;
+        }
+        let t = net.params().t() as usize;
+        let dst_list = net.random_neighbours(message.to, t);
+        forward(net, &message, &dst_list, ts);
+    }
+}
+
+/// record `message` at its receiver, `true` if the receiver should forward it: the first
+/// time it gets it, or within `max_hop_num` hops and its spam budget for a spammer. Droppers
+/// swallow it.
+fn first_receipt(net: &mut Network, message: &Message, ts: TimeStamp) -> bool {
+    let node = message.to;
+    let behaviour = net.behaviours().of(node);
+    let max_hop_num = net.params().max_hop_num();
+    let status = net.node_status_mut(message.message_id, node);
+    status.record_recv_message(ts);
+    let first = status.recv_message_count() == 1;
+    match behaviour {
+        Behaviour::Dropper => {
+            net.behaviours_mut().record_swallowed(message.message_id);
+            false
+        }
+        Behaviour::Spammer => message.hop_num < max_hop_num && status.spend_spam_budget(),
+        _ => first,
+    }
+}
+
+/// send full message of the next hop from `message.to` to each of `dst_list`.
+fn forward(net: &mut Network, message: &Message, dst_list: &[NodeId], ts: TimeStamp) {
+    let mut send_message =
+        Message::build_send_full_message(message.to, message.to, message.hop_num + 1);
+    send_message.message_id = message.message_id;
+    for dst in dst_list {
+        send_message.to = *dst;
+        let (next_ts, res) = net.send(send_message.clone(), ts, DelayKind::Message);
+        log::debug!(
+            "  -> forward msg {:?} to {} ts: {}  res:{}",
+            send_message,
+            dst,
+            next_ts,
+            res
+        );
+    }
+}
+
+#[test]
+fn test_flood() {
+    use crate::params_packet::ParamsPacket;
+
+    let params = ParamsPacket::builder(50, 4, 3, 5).seed(3).build().unwrap();
+    let mut net = Network::for_message(params, 0);
+    // the source sends to all 49 others, every other node to all but the one it got it from
+    Flood.on_receive(&mut net, Message::build_send_full_message(0, 0, 0), 0);
+    let sent = net.drain_messages();
+    assert_eq!(sent.len(), 49);
+    assert!(sent
+        .iter()
+        .all(|m| m.from == 0 && m.hop_num == 1 && m.to != 0));
+    Flood.on_receive(&mut net, Message::build_send_full_message(0, 1, 1), 100);
+    let sent = net.drain_messages();
+    assert_eq!(sent.len(), 48);
+    assert!(sent
+        .iter()
+        .all(|m| m.to != 0 && m.to != 1 && m.hop_num == 2));
+    // only once
+    Flood.on_receive(&mut net, Message::build_send_full_message(2, 1, 1), 200);
+    assert!(net.drain_messages().is_empty());
+    assert_eq!(net.node_status(0)[1].recv_message_count(), 2);
+}
+
+#[test]
+fn test_push_gossip() {
+    use crate::params_packet::ParamsPacket;
+
+    let params = ParamsPacket::builder(50, 4, 3, 5).seed(3).build().unwrap();
+    let mut net = Network::for_message(params, 0);
+    let mut push = PushGossip { ttl: 2 };
+    push.on_receive(&mut net, Message::build_send_full_message(0, 0, 0), 0);
+    let sent = net.drain_messages();
+    assert_eq!(sent.len(), 4);
+    assert!(sent.iter().all(|m| m.hop_num == 1));
+    push.on_receive(&mut net, Message::build_send_full_message(0, 1, 1), 100);
+    assert_eq!(net.drain_messages().len(), 4);
+    push.on_receive(&mut net, Message::build_send_full_message(1, 1, 1), 100);
+    assert!(net.drain_messages().is_empty());
+    // it took `ttl` hops
+    push.on_receive(&mut net, Message::build_send_full_message(1, 2, 2), 200);
+    assert!(net.drain_messages().is_empty());
+}
+
+#[test]
+fn test_baseline_behaviours() {
+    use crate::{behaviour::SPAM_BUDGET, params_packet::ParamsPacket};
+
+    let params = ParamsPacket::builder(50, 4, 3, 5)
+        .seed(3)
+        .behaviours("dropper:0.2,spammer:0.2".parse().unwrap())
+        .build()
+        .unwrap();
+    let mut net = Network::for_message(params, 0);
+    let node_of = |net: &Network, behaviour| {
+        (1..50)
+            .find(|node| net.behaviours().of(*node) == behaviour)
+            .unwrap()
+    };
+
+    let dropper = node_of(&net, Behaviour::Dropper);
+    Flood.on_receive(&mut net, Message::build_send_full_message(0, dropper, 1), 0);
+    assert!(net.drain_messages().is_empty());
+    assert_eq!(net.behaviours().swallowed_count(0), 1);
+
+    // a spammer pushes each copy within `max_hop_num` hops, until its budget is spent
+    let spammer = node_of(&net, Behaviour::Spammer);
+    let mut push = PushGossip { ttl: 100 };
+    for _ in 0..SPAM_BUDGET {
+        push.on_receive(&mut net, Message::build_send_full_message(0, spammer, 1), 0);
+        assert_eq!(net.drain_messages().len(), 4);
+    }
+    push.on_receive(&mut net, Message::build_send_full_message(0, spammer, 1), 0);
+    assert!(net.drain_messages().is_empty());
+}
@@ -0,0 +1,283 @@
+use std::{fmt, str::FromStr};
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    message::{counter_of, MessageId, NodeId},
+    sim_rng::{stream_rng, SimRng, BEHAVIOUR_STREAM},
+};
+
+/// How a node deals with the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Behaviour {
+    #[default]
+    Honest,
+    /// recvs everything, forwards nothing and answers no ask for.
+    Dropper,
+    /// forwards full messages and hashes, but never answers `AskForMessage`.
+    NoReply,
+    /// forwards full messages claiming in `bloomstatus` that every node has it already.
+    BloomLiar,
+    /// ignores `each_handle_count` and bloom filters, and keeps sending full message
+    /// instead of hashes after `k` rounds, up to `SPAM_BUDGET` copies of each message.
+    Spammer,
+}
+
+/// copies of each message a spammer forwards at most. Without a cap, spammers forwarding each
+/// other's copies grow by a factor of about `t * spammer` with every hop.
+pub const SPAM_BUDGET: u32 = 8;
+
+impl Behaviour {
+    const NAMES: [(&'static str, Behaviour); 4] = [
+        ("dropper", Behaviour::Dropper),
+        ("no-reply", Behaviour::NoReply),
+        ("bloom-liar", Behaviour::BloomLiar),
+        ("spammer", Behaviour::Spammer),
+    ];
+
+    /// answers `AskForMessage` when it has the full message.
+    pub fn replies_ask_for(&self) -> bool {
+        !matches!(self, Behaviour::Dropper | Behaviour::NoReply)
+    }
+}
+
+/// Fraction of nodes (apart from the source) with each misbehaviour, the rest are honest.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub struct BehaviourMix {
+    pub dropper: f64,
+    pub no_reply: f64,
+    pub bloom_liar: f64,
+    pub spammer: f64,
+}
+
+impl BehaviourMix {
+    fn fractions(&self) -> [(Behaviour, f64); 4] {
+        [
+            (Behaviour::Dropper, self.dropper),
+            (Behaviour::NoReply, self.no_reply),
+            (Behaviour::BloomLiar, self.bloom_liar),
+            (Behaviour::Spammer, self.spammer),
+        ]
+    }
+
+    fn fraction_mut(&mut self, behaviour: Behaviour) -> &mut f64 {
+        match behaviour {
+            Behaviour::Dropper => &mut self.dropper,
+            Behaviour::NoReply => &mut self.no_reply,
+            Behaviour::BloomLiar => &mut self.bloom_liar,
+            Behaviour::Spammer => &mut self.spammer,
+            Behaviour::Honest => unreachable!("honest is the rest"),
+        }
+    }
+
+    pub fn is_honest(&self) -> bool {
+        self.fractions()
+            .iter()
+            .all(|(_, fraction)| *fraction == 0.0)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let fractions = self.fractions();
+        if fractions.iter().any(|(_, f)| !(0.0..=1.0).contains(f)) {
+            return Err(format!("{}: fractions must be in [0, 1]", self));
+        }
+        if fractions.iter().map(|(_, f)| f).sum::<f64>() >= 1.0 {
+            return Err(format!("{}: fractions must sum to less than 1", self));
+        }
+        Ok(())
+    }
+}
+
+/// `honest` or comma separated `<behaviour>:<fraction>`, behaviour one of
+/// `dropper`, `no-reply`, `bloom-liar`, `spammer`.
+impl FromStr for BehaviourMix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = BehaviourMix::default();
+        if s == "honest" {
+            return Ok(mix);
+        }
+        for part in s.split(',') {
+            let (name, fraction) = part
+                .split_once(':')
+                .ok_or_else(|| format!("expect <behaviour>:<fraction>, got `{}`", part))?;
+            let behaviour = Behaviour::NAMES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, b)| *b)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown behaviour `{}`, expect dropper, no-reply, bloom-liar or spammer",
+                        name
+                    )
+                })?;
+            *mix.fraction_mut(behaviour) =
+                fraction.parse().map_err(|e| format!("{} in `{}`", e, s))?;
+        }
+        Ok(mix)
+    }
+}
+
+impl TryFrom<String> for BehaviourMix {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for BehaviourMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_honest() {
+            return write!(f, "honest");
+        }
+        let parts: Vec<String> = Behaviour::NAMES
+            .iter()
+            .zip(self.fractions())
+            .filter(|(_, (_, fraction))| *fraction > 0.0)
+            .map(|((name, _), (_, fraction))| format!("{}:{}", name, fraction))
+            .collect();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// Behaviour of every node while one message spreads, and what misbehaving cost.
+#[derive(Debug, Clone)]
+pub struct Behaviours {
+    mix: BehaviourMix,
+    behaviour: Vec<Behaviour>,
+    /// of each message id: full messages and hashes recvd by droppers.
+    swallowed_count: Vec<u32>,
+    /// ask fors not answered although the asked node had full message.
+    unanswered_ask_for_count: Vec<u32>,
+    /// full messages sent with a lying `bloomstatus`.
+    lie_count: Vec<u32>,
+    /// messages sent by spammers.
+    spam_count: Vec<u32>,
+    rng: SimRng,
+}
+
+// getter
+impl Behaviours {
+    pub fn swallowed_count(&self, message_id: MessageId) -> u32 {
+        self.swallowed_count.get(message_id).copied().unwrap_or(0)
+    }
+    pub fn unanswered_ask_for_count(&self, message_id: MessageId) -> u32 {
+        self.unanswered_ask_for_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
+    }
+    pub fn lie_count(&self, message_id: MessageId) -> u32 {
+        self.lie_count.get(message_id).copied().unwrap_or(0)
+    }
+    pub fn spam_count(&self, message_id: MessageId) -> u32 {
+        self.spam_count.get(message_id).copied().unwrap_or(0)
+    }
+    pub fn behaviour(&self) -> &[Behaviour] {
+        &self.behaviour
+    }
+}
+
+impl Behaviours {
+    pub fn new(node_size: usize, mix: BehaviourMix) -> Behaviours {
+        Behaviours {
+            mix,
+            behaviour: vec![Behaviour::Honest; node_size],
+            swallowed_count: Vec::new(),
+            unanswered_ask_for_count: Vec::new(),
+            lie_count: Vec::new(),
+            spam_count: Vec::new(),
+            rng: stream_rng(0, BEHAVIOUR_STREAM),
+        }
+    }
+
+    /// draw the behaviours from `seed`, `source` is always honest.
+    pub fn reset(&mut self, seed: u64, source: NodeId) {
+        self.rng = stream_rng(seed, BEHAVIOUR_STREAM);
+        self.swallowed_count.clear();
+        self.unanswered_ask_for_count.clear();
+        self.lie_count.clear();
+        self.spam_count.clear();
+        self.behaviour
+            .iter_mut()
+            .for_each(|b| *b = Behaviour::Honest);
+        if self.mix.is_honest() {
+            return;
+        }
+
+        let node_size = self.behaviour.len();
+        let mut candidates: Vec<NodeId> = (0..node_size).filter(|n| *n != source).collect();
+        candidates.shuffle(&mut self.rng);
+        let mut candidates = candidates.into_iter();
+        for (behaviour, fraction) in self.mix.fractions() {
+            let size = (fraction * (node_size - 1) as f64).round() as usize;
+            for node in candidates.by_ref().take(size) {
+                self.behaviour[node] = behaviour;
+            }
+        }
+    }
+
+    pub fn of(&self, node: NodeId) -> Behaviour {
+        self.behaviour[node]
+    }
+
+    pub fn record_swallowed(&mut self, message_id: MessageId) {
+        *counter_of(&mut self.swallowed_count, message_id) += 1;
+    }
+
+    pub fn record_unanswered_ask_for(&mut self, message_id: MessageId) {
+        *counter_of(&mut self.unanswered_ask_for_count, message_id) += 1;
+    }
+
+    pub fn record_lie(&mut self, message_id: MessageId) {
+        *counter_of(&mut self.lie_count, message_id) += 1;
+    }
+
+    pub fn record_spam(&mut self, message_id: MessageId) {
+        *counter_of(&mut self.spam_count, message_id) += 1;
+    }
+}
+
+#[test]
+fn test_parse_behaviour_mix() {
+    assert_eq!("honest".parse(), Ok(BehaviourMix::default()));
+    let mix: BehaviourMix = "dropper:0.1,spammer:0.05".parse().unwrap();
+    assert_eq!(
+        mix,
+        BehaviourMix {
+            dropper: 0.1,
+            spammer: 0.05,
+            ..Default::default()
+        }
+    );
+    assert_eq!(mix.to_string(), "dropper:0.1,spammer:0.05");
+    assert_eq!(BehaviourMix::default().to_string(), "honest");
+    assert!("liar:0.1".parse::<BehaviourMix>().is_err());
+    assert!("dropper".parse::<BehaviourMix>().is_err());
+    assert!("dropper:0.6,no-reply:0.4"
+        .parse::<BehaviourMix>()
+        .unwrap()
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn test_assign_behaviours() {
+    let mix: BehaviourMix = "dropper:0.1,no-reply:0.2,bloom-liar:0.05".parse().unwrap();
+    let mut behaviours = Behaviours::new(101, mix);
+    behaviours.reset(5, 7);
+    let count = |b: Behaviour| behaviours.behaviour().iter().filter(|x| **x == b).count();
+    assert_eq!(count(Behaviour::Dropper), 10);
+    assert_eq!(count(Behaviour::NoReply), 20);
+    assert_eq!(count(Behaviour::BloomLiar), 5);
+    assert_eq!(count(Behaviour::Spammer), 0);
+    assert_eq!(behaviours.of(7), Behaviour::Honest);
+
+    let assigned = behaviours.behaviour().to_vec();
+    behaviours.reset(5, 7);
+    assert_eq!(behaviours.behaviour(), assigned);
+}
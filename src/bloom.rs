@@ -0,0 +1,275 @@
+use std::{collections::HashSet, fmt, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::message::{NodeId, NODE_ID_SIZE};
+
+/// How `Message::bloomstatus` and each node's view of who has the message are kept.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "String")]
+pub enum BloomKind {
+    /// exact node id set, never a false positive. Costs one node id per entry on the wire.
+    #[default]
+    Exact,
+    /// bloom filter of `bits` bits and `hashes` hash functions.
+    Bloom { bits: u32, hashes: u32 },
+}
+
+impl BloomKind {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            BloomKind::Exact => Ok(()),
+            BloomKind::Bloom { bits, hashes } => {
+                if bits == 0 || hashes == 0 {
+                    Err(format!(
+                        "bloom filter needs at least 1 bit and 1 hash, got bloom:{}:{}",
+                        bits, hashes
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// an empty filter of this kind.
+    pub fn new_filter(&self) -> NodeFilter {
+        match *self {
+            BloomKind::Exact => NodeFilter::Exact(HashSet::new()),
+            BloomKind::Bloom { bits, hashes } => NodeFilter::Bloom(BloomFilter::new(bits, hashes)),
+        }
+    }
+}
+
+/// `exact` or `bloom:<bits>:<hashes>`.
+impl FromStr for BloomKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let parse = |v: &str| v.parse::<u32>().map_err(|e| format!("{} in `{}`", e, s));
+        match parts.as_slice() {
+            ["exact"] => Ok(BloomKind::Exact),
+            ["bloom", bits, hashes] => Ok(BloomKind::Bloom {
+                bits: parse(bits)?,
+                hashes: parse(hashes)?,
+            }),
+            _ => Err(format!(
+                "unknown bloom filter `{}`, expect exact or bloom:<bits>:<hashes>",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for BloomKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for BloomKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BloomKind::Exact => write!(f, "exact"),
+            BloomKind::Bloom { bits, hashes } => write!(f, "bloom:{}:{}", bits, hashes),
+        }
+    }
+}
+
+/// Set of nodes known to have (or be about to get) the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeFilter {
+    Exact(HashSet<NodeId>),
+    Bloom(BloomFilter),
+}
+
+impl Default for NodeFilter {
+    fn default() -> Self {
+        NodeFilter::Exact(HashSet::new())
+    }
+}
+
+impl NodeFilter {
+    pub fn insert(&mut self, node: NodeId) {
+        match self {
+            NodeFilter::Exact(set) => {
+                set.insert(node);
+            }
+            NodeFilter::Bloom(bloom) => bloom.insert(node),
+        }
+    }
+
+    /// may be a false positive for bloom filters.
+    pub fn contains(&self, node: NodeId) -> bool {
+        match self {
+            NodeFilter::Exact(set) => set.contains(&node),
+            NodeFilter::Bloom(bloom) => bloom.contains(node),
+        }
+    }
+
+    /// `node` is in the filter without ever being inserted.
+    pub fn is_false_positive(&self, node: NodeId) -> bool {
+        match self {
+            NodeFilter::Exact(_) => false,
+            NodeFilter::Bloom(bloom) => bloom.contains(node) && !bloom.inserted.contains(&node),
+        }
+    }
+
+    /// add every node of `other` (of the same kind).
+    pub fn merge(&mut self, other: &NodeFilter) {
+        match (self, other) {
+            (NodeFilter::Exact(set), NodeFilter::Exact(other)) => set.extend(other.iter()),
+            (NodeFilter::Bloom(bloom), NodeFilter::Bloom(other)) => bloom.merge(other),
+            (this, other) => panic!("merge {:?} into a filter of other kind {:?}", other, this),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        match self {
+            NodeFilter::Exact(set) => set.clear(),
+            NodeFilter::Bloom(bloom) => bloom.clear(),
+        }
+    }
+
+    /// how many nodes were inserted.
+    pub fn len(&self) -> usize {
+        match self {
+            NodeFilter::Exact(set) => set.len(),
+            NodeFilter::Bloom(bloom) => bloom.inserted.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// bytes of this filter in a message.
+    pub fn wire_size(&self) -> u64 {
+        match self {
+            NodeFilter::Exact(set) => set.len() as u64 * NODE_ID_SIZE as u64,
+            NodeFilter::Bloom(bloom) => (bloom.bit_count as u64).div_ceil(8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u32,
+    hash_count: u32,
+    /// nodes really inserted, only to tell false positives apart. Not part of the wire size.
+    inserted: HashSet<NodeId>,
+}
+
+impl BloomFilter {
+    pub fn new(bit_count: u32, hash_count: u32) -> BloomFilter {
+        BloomFilter {
+            bits: vec![0; (bit_count as usize).div_ceil(64)],
+            bit_count,
+            hash_count,
+            inserted: HashSet::new(),
+        }
+    }
+
+    pub fn insert(&mut self, node: NodeId) {
+        for bit in self.bit_indexes(node) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.inserted.insert(node);
+    }
+
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.bit_indexes(node)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn merge(&mut self, other: &BloomFilter) {
+        assert_eq!(
+            (self.bit_count, self.hash_count),
+            (other.bit_count, other.hash_count)
+        );
+        self.bits
+            .iter_mut()
+            .zip(other.bits.iter())
+            .for_each(|(a, b)| *a |= b);
+        self.inserted.extend(other.inserted.iter());
+    }
+
+    pub fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|w| *w = 0);
+        self.inserted.clear();
+    }
+
+    /// double hashing `h1 + i * h2`, both derived from the node id.
+    fn bit_indexes(&self, node: NodeId) -> impl Iterator<Item = usize> {
+        let h1 = splitmix64(node as u64);
+        let h2 = splitmix64(h1) | 1;
+        let bit_count = self.bit_count as u64;
+        (0..self.hash_count as u64)
+            .map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bit_count) as usize)
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[test]
+fn test_parse_bloom_kind() {
+    assert_eq!("exact".parse(), Ok(BloomKind::Exact));
+    assert_eq!(
+        "bloom:1024:3".parse(),
+        Ok(BloomKind::Bloom {
+            bits: 1024,
+            hashes: 3
+        })
+    );
+    for s in ["exact", "bloom:64:2"] {
+        assert_eq!(s.parse::<BloomKind>().unwrap().to_string(), s);
+    }
+    assert!("bloom:64".parse::<BloomKind>().is_err());
+    assert!(BloomKind::Bloom { bits: 0, hashes: 1 }.validate().is_err());
+}
+
+#[test]
+fn test_bloom_filter() {
+    let mut bloom = BloomKind::Bloom {
+        bits: 256,
+        hashes: 3,
+    }
+    .new_filter();
+    (0..20).for_each(|node| bloom.insert(node));
+    // no false negatives
+    assert!((0..20).all(|node| bloom.contains(node) && !bloom.is_false_positive(node)));
+    assert_eq!(bloom.len(), 20);
+    assert_eq!(bloom.wire_size(), 32);
+
+    // a tiny filter is full of false positives
+    let mut tiny = BloomKind::Bloom { bits: 8, hashes: 2 }.new_filter();
+    (0..20).for_each(|node| tiny.insert(node));
+    let false_positives = (20..1000).filter(|n| tiny.is_false_positive(*n)).count();
+    assert!(false_positives > 900);
+
+    let mut other = BloomKind::Bloom {
+        bits: 256,
+        hashes: 3,
+    }
+    .new_filter();
+    other.insert(500);
+    bloom.merge(&other);
+    assert!(bloom.contains(500) && !bloom.is_false_positive(500));
+
+    bloom.clear();
+    assert!(bloom.is_empty() && !bloom.contains(0));
+
+    let mut exact = NodeFilter::default();
+    exact.insert(3);
+    assert!(exact.contains(3) && !exact.contains(4));
+    assert_eq!(exact.wire_size(), NODE_ID_SIZE as u64);
+}
@@ -0,0 +1,225 @@
+use crate::{
+    behaviour::Behaviour,
+    latency::DelayKind,
+    message::{Chunk, Message, MessageStatus, NodeId},
+    message_queue::TimeStamp,
+    network::Network,
+    protocol::Protocol,
+    rrs::Rrs,
+};
+
+/// RRS with an erasure-coded push. The source codes the payload into `n` chunks, any `m` of
+/// which rebuild it, and sends each to `t` random neighbours. Every node pushes each chunk it
+/// gets for the first time on to `t` random neighbours for the first `k` hops, and has the
+/// message once it holds `m` distinct chunks, or the whole payload in reply to an ask for.
+/// Then it announces the hash, which spreads as in RRS: a node that hears it without the
+/// message asks a random neighbour, and one that has it replies with the whole payload.
+#[derive(Debug, Clone, Copy)]
+pub struct ErasureCoded {
+    pub m: u32,
+    pub n: u32,
+}
+
+impl Protocol for ErasureCoded {
+    fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, message_id) = (message.to, message.message_id);
+        match (&message.status, message.chunk) {
+            (MessageStatus::FullMessage, Some(chunk)) => self.on_chunk(net, message, chunk, ts),
+            // the source gets the whole payload from itself and codes it
+            (MessageStatus::FullMessage, None) if message.from == node => {
+                net.node_status_mut(message_id, node)
+                    .record_recv_message(ts);
+                let t = net.params().t() as usize;
+                for index in 0..self.n {
+                    let chunk = Chunk { index, m: self.m };
+                    for dst in net.random_neighbours(node, t) {
+                        send(net, node, dst, &message, Some(chunk), ts);
+                    }
+                }
+            }
+            // the whole payload in reply to an ask for
+            (MessageStatus::FullMessage, None) => {
+                let status = net.node_status_mut(message_id, node);
+                let first = !status.has_recv_full_message();
+                status.record_recv_message(ts);
+                if net.behaviours().of(node) == Behaviour::Dropper {
+                    net.behaviours_mut().record_swallowed(message_id);
+                } else if first {
+                    announce(net, &message, ts);
+                }
+            }
+            _ => Rrs.on_receive(net, message, ts),
+        }
+    }
+}
+
+impl ErasureCoded {
+    fn on_chunk(&mut self, net: &mut Network, message: Message, chunk: Chunk, ts: TimeStamp) {
+        let (node, message_id) = (message.to, message.message_id);
+        let behaviour = net.behaviours().of(node);
+        let status = net.node_status_mut(message_id, node);
+        let new = status.record_recv_chunk(chunk.index, ts);
+        let rebuilt = !status.has_recv_full_message() && status.recv_chunk_count() >= chunk.m;
+        if rebuilt {
+            log::debug!("  {} rebuilt message {} at {}", node, message_id, ts);
+            status.record_recv_message(ts);
+        }
+        if behaviour == Behaviour::Dropper {
+            net.behaviours_mut().record_swallowed(message_id);
+            return;
+        }
+        let t = net.params().t() as usize;
+        let push = match behaviour {
+            Behaviour::Spammer => {
+                message.hop_num < net.params().max_hop_num()
+                    && net.node_status_mut(message_id, node).spend_spam_budget()
+            }
+            _ => new && message.hop_num < net.params().k(),
+        };
+        if push {
+            for dst in net.random_neighbours(node, t) {
+                if dst != message.from {
+                    send(net, node, dst, &message, Some(chunk), ts);
+                }
+            }
+        }
+        if rebuilt {
+            announce(net, &message, ts);
+        }
+    }
+}
+
+/// announce the hash to `t` random neighbours of `message.to`, which just got the message.
+fn announce(net: &mut Network, message: &Message, ts: TimeStamp) {
+    let node = message.to;
+    let t = net.params().t() as usize;
+    let mut announce = Message::build_send_hash_message(node, node, message.hop_num + 1);
+    announce.message_id = message.message_id;
+    for dst in net.random_neighbours(node, t) {
+        announce.to = dst;
+        net.send(announce.clone(), ts, DelayKind::Hash);
+    }
+}
+
+/// send `chunk` of `message` (or the whole payload) from `from` to `dst`, one hop further.
+fn send(
+    net: &mut Network,
+    from: NodeId,
+    dst: NodeId,
+    message: &Message,
+    chunk: Option<Chunk>,
+    ts: TimeStamp,
+) {
+    let mut send_message = Message::build_send_full_message(from, dst, message.hop_num + 1);
+    send_message.message_id = message.message_id;
+    send_message.chunk = chunk;
+    let (next_ts, res) = net.send(send_message.clone(), ts, DelayKind::Message);
+    log::debug!(
+        "  -> send chunk {:?} to {} ts: {}  res:{}",
+        send_message,
+        dst,
+        next_ts,
+        res
+    );
+}
+
+#[test]
+fn test_erasure_source() {
+    use crate::params_packet::ParamsPacket;
+
+    let params = ParamsPacket::builder(100, 4, 4, 1).seed(1).build().unwrap();
+    let mut net = Network::for_message(params, 0);
+    let mut coded = ErasureCoded { m: 4, n: 8 };
+    // each of the `n` chunks to `t` random neighbours
+    coded.on_receive(&mut net, Message::build_send_full_message(0, 0, 0), 0);
+    let sent = net.drain_messages();
+    assert_eq!(sent.len(), 8 * 4);
+    for index in 0..8 {
+        let copies: Vec<&Message> = sent
+            .iter()
+            .filter(|m| m.chunk == Some(Chunk { index, m: 4 }))
+            .collect();
+        assert_eq!(copies.len(), 4);
+        assert!(copies.iter().all(|m| m.from == 0 && m.hop_num == 1));
+    }
+}
+
+#[test]
+fn test_erasure_rebuild() {
+    use crate::params_packet::ParamsPacket;
+
+    let params = ParamsPacket::builder(100, 4, 2, 1).seed(1).build().unwrap();
+    let mut net = Network::for_message(params, 0);
+    let mut coded = ErasureCoded { m: 3, n: 6 };
+    let node = 50;
+    let chunk = |index, hop_num| {
+        let mut message = Message::build_send_full_message(0, node, hop_num);
+        message.chunk = Some(Chunk { index, m: 3 });
+        message
+    };
+
+    // a new chunk is pushed on within `k` hops
+    coded.on_receive(&mut net, chunk(0, 1), 100);
+    let pushed = net.drain_messages();
+    assert!(!pushed.is_empty() && pushed.len() <= 4);
+    assert!(pushed
+        .iter()
+        .all(|m| m.chunk == Some(Chunk { index: 0, m: 3 }) && m.hop_num == 2 && m.to != 0));
+    // a copy neither counts nor goes on
+    coded.on_receive(&mut net, chunk(0, 1), 110);
+    assert!(net.drain_messages().is_empty());
+    coded.on_receive(&mut net, chunk(1, 2), 120);
+    assert!(net.drain_messages().is_empty());
+    assert_eq!(net.node_status(0)[node].recv_chunk_count(), 2);
+    assert!(!net.node_status(0)[node].has_recv_full_message());
+
+    // the `m`th distinct chunk rebuilds it, and the hash is announced to `t` neighbours
+    coded.on_receive(&mut net, chunk(5, 2), 130);
+    assert!(net.node_status(0)[node].has_recv_full_message());
+    let announced = net.drain_messages();
+    assert_eq!(announced.len(), 4);
+    assert!(announced
+        .iter()
+        .all(|m| m.status == MessageStatus::OnlyHash && m.from == node && m.hop_num == 3));
+    // only once
+    coded.on_receive(&mut net, chunk(4, 2), 140);
+    assert!(net.drain_messages().is_empty());
+}
+
+#[test]
+fn test_erasure_reply() {
+    use crate::params_packet::ParamsPacket;
+
+    let params = ParamsPacket::builder(100, 4, 2, 1)
+        .seed(1)
+        .behaviours("dropper:0.2".parse().unwrap())
+        .build()
+        .unwrap();
+    let mut net = Network::for_message(params, 0);
+    let mut coded = ErasureCoded { m: 3, n: 6 };
+    let node = (1..100)
+        .find(|node| net.behaviours().of(*node) == Behaviour::Honest)
+        .unwrap();
+
+    // a node that gets the whole payload in reply announces it like one that rebuilt it
+    coded.on_receive(&mut net, Message::build_send_full_message(0, node, 0), 100);
+    assert!(net.node_status(0)[node].has_recv_full_message());
+    let announced = net.drain_messages();
+    assert_eq!(announced.len(), 4);
+    assert!(announced
+        .iter()
+        .all(|m| m.status == MessageStatus::OnlyHash && m.from == node && m.hop_num == 1));
+    coded.on_receive(&mut net, Message::build_send_full_message(0, node, 0), 200);
+    assert!(net.drain_messages().is_empty());
+
+    let dropper = (1..100)
+        .find(|node| net.behaviours().of(*node) == Behaviour::Dropper)
+        .unwrap();
+    coded.on_receive(
+        &mut net,
+        Message::build_send_full_message(0, dropper, 0),
+        100,
+    );
+    assert!(net.drain_messages().is_empty());
+    assert_eq!(net.behaviours().swallowed_count(0), 1);
+}
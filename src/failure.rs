@@ -0,0 +1,186 @@
+use rand::prelude::*;
+
+use crate::{
+    message::{counter_of, MessageId, NodeId},
+    message_queue::TimeStamp,
+    sim_rng::{stream_rng, SimRng, FAILURE_STREAM},
+};
+
+/// Which nodes are online while one message spreads.
+///
+/// `offline_fraction` of the nodes (never the source) are offline from the start. With churn,
+/// every node toggles between online and offline after exponentially distributed times,
+/// `churn_rate` toggles per node per second on average. A node coming back keeps what it
+/// has recvd before crashing.
+#[derive(Debug, Clone)]
+pub struct Failures {
+    offline_fraction: f64,
+    /// toggles per node per ms.
+    churn_rate: f64,
+    online: Vec<bool>,
+    next_toggle_ts: Vec<f64>,
+    /// first ms each crash/join is seen at.
+    crash_ts: Vec<TimeStamp>,
+    join_ts: Vec<TimeStamp>,
+    /// messages of each message id that arrived at an offline node.
+    offline_drop_count: Vec<u32>,
+    rng: SimRng,
+}
+
+// getter
+impl Failures {
+    /// crashes seen at a ts within `from..=to`.
+    pub fn crash_count(&self, from: TimeStamp, to: TimeStamp) -> u32 {
+        count_within(&self.crash_ts, from, to)
+    }
+    /// joins seen at a ts within `from..=to`.
+    pub fn join_count(&self, from: TimeStamp, to: TimeStamp) -> u32 {
+        count_within(&self.join_ts, from, to)
+    }
+    pub fn offline_drop_count(&self, message_id: MessageId) -> u32 {
+        self.offline_drop_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
+    }
+    /// online flag of every node, as of the last [`Failures::advance_to`].
+    pub fn online(&self) -> &[bool] {
+        &self.online
+    }
+}
+
+impl Failures {
+    pub fn new(node_size: usize, offline_fraction: f64, churn_rate: f64) -> Failures {
+        Failures {
+            offline_fraction,
+            churn_rate: churn_rate / 1000.0,
+            online: vec![true; node_size],
+            next_toggle_ts: vec![f64::INFINITY; node_size],
+            crash_ts: Vec::new(),
+            join_ts: Vec::new(),
+            offline_drop_count: Vec::new(),
+            rng: stream_rng(0, FAILURE_STREAM),
+        }
+    }
+
+    /// draw the failures from `seed`, `source` is sure to be online.
+    pub fn reset(&mut self, seed: u64, source: NodeId) {
+        self.rng = stream_rng(seed, FAILURE_STREAM);
+        self.crash_ts.clear();
+        self.join_ts.clear();
+        self.offline_drop_count.clear();
+        self.online.iter_mut().for_each(|online| *online = true);
+
+        let node_size = self.online.len();
+        let offline_size = (self.offline_fraction * (node_size - 1) as f64).round() as usize;
+        let candidates: Vec<NodeId> = (0..node_size).filter(|n| *n != source).collect();
+        for node in candidates.choose_multiple(&mut self.rng, offline_size) {
+            self.online[*node] = false;
+        }
+        for node in 0..node_size {
+            self.next_toggle_ts[node] = self.draw_toggle_ts(0.0);
+        }
+    }
+
+    /// if `node` is online at `ts`. `ts` must not go back for the same node.
+    pub fn is_online(&mut self, node: NodeId, ts: TimeStamp) -> bool {
+        while self.next_toggle_ts[node] <= ts as f64 {
+            let toggle_ts = self.next_toggle_ts[node];
+            if self.online[node] {
+                self.crash_ts.push(toggle_ts.ceil() as TimeStamp);
+            } else {
+                self.join_ts.push(toggle_ts.ceil() as TimeStamp);
+            }
+            self.online[node] = !self.online[node];
+            let next_toggle_ts = self.draw_toggle_ts(toggle_ts);
+            // a draw too short to move the ts would toggle forever, toggle a ms later instead
+            self.next_toggle_ts[node] = if next_toggle_ts > toggle_ts {
+                next_toggle_ts
+            } else {
+                toggle_ts + 1.0
+            };
+        }
+        self.online[node]
+    }
+
+    /// ts `node`, offline as of the last [`Failures::is_online`], comes back at, `None` if never.
+    pub fn rejoin_ts(&self, node: NodeId) -> Option<TimeStamp> {
+        let ts = self.next_toggle_ts[node].ceil();
+        (!self.online[node] && ts <= TimeStamp::MAX as f64).then_some(ts as TimeStamp)
+    }
+
+    pub fn record_offline_drop(&mut self, message_id: MessageId) {
+        *counter_of(&mut self.offline_drop_count, message_id) += 1;
+    }
+
+    /// bring every node to its state at `ts`.
+    pub fn advance_to(&mut self, ts: TimeStamp) {
+        for node in 0..self.online.len() {
+            self.is_online(node, ts);
+        }
+    }
+
+    fn draw_toggle_ts(&mut self, from: f64) -> f64 {
+        if self.churn_rate == 0.0 {
+            return f64::INFINITY;
+        }
+        let u: f64 = self.rng.gen();
+        from - (1.0 - u).ln() / self.churn_rate
+    }
+}
+
+fn count_within(ts_list: &[TimeStamp], from: TimeStamp, to: TimeStamp) -> u32 {
+    ts_list.iter().filter(|ts| (from..=to).contains(ts)).count() as u32
+}
+
+#[test]
+fn test_offline_fraction() {
+    let mut failures = Failures::new(101, 0.2, 0.0);
+    failures.reset(7, 3);
+    failures.advance_to(1_000_000);
+    let live = failures.online().to_vec();
+    assert_eq!(live.iter().filter(|online| !**online).count(), 20);
+    assert!(live[3]);
+    assert_eq!(failures.crash_count(0, 1_000_000), 0);
+
+    let mut same = Failures::new(101, 0.2, 0.0);
+    same.reset(7, 3);
+    same.advance_to(0);
+    assert_eq!(same.online(), live);
+}
+
+#[test]
+fn test_churn() {
+    // one toggle per node per second on average
+    let mut failures = Failures::new(1000, 0.0, 1.0);
+    failures.reset(1, 0);
+    failures.advance_to(0);
+    assert!(failures.online().iter().all(|online| *online));
+    failures.advance_to(100);
+    let live = failures.online();
+    // about 1 - e^-0.1 crashed
+    let offline = live.iter().filter(|online| !**online).count();
+    assert!((60..150).contains(&offline));
+    assert_eq!(
+        failures.crash_count(0, 100) - failures.join_count(0, 100),
+        offline as u32
+    );
+    // crashes are told apart by when they are seen
+    failures.advance_to(200);
+    let crash_count = failures.crash_count(0, 200);
+    assert!(crash_count > failures.crash_count(0, 100));
+    assert_eq!(
+        failures.crash_count(0, 100) + failures.crash_count(101, 200),
+        crash_count
+    );
+}
+
+#[test]
+fn test_toggle_ts_advances() {
+    // churn so fast that each draw adds nothing to the ts
+    let mut failures = Failures::new(1, 0.0, f64::INFINITY);
+    failures.reset(1, 0);
+    assert!(!failures.is_online(0, 10));
+    // toggled at 0, 1, ..., 10
+    assert_eq!(failures.crash_count(0, 10) + failures.join_count(0, 10), 11);
+}
@@ -0,0 +1,529 @@
+use std::collections::{BTreeSet, HashMap};
+
+use rand::prelude::*;
+
+use crate::{
+    behaviour::Behaviour,
+    message::{Message, MessageId, MessageStatus, NodeId},
+    message_queue::{TimeStamp, Timer},
+    network::Network,
+    protocol::{send, Protocol},
+    sim_rng::{stream_rng, PROTOCOL_STREAM},
+};
+
+/// heartbeats a message is announced for after it was received, `mcache_gossip` of libp2p.
+pub const HISTORY_GOSSIP: u32 = 3;
+/// first deliveries of a peer counted in its score.
+const FIRST_DELIVERY_CAP: u32 = 10;
+/// messages a peer has been in the mesh for before missing them all counts against it.
+const MESH_ACTIVATION: u32 = 5;
+
+/// Mesh gossip like libp2p gossipsub v1.1. Each node pushes the full message to its mesh
+/// peers, kept between `d_lo` and `d_hi` (back to `d`) with GRAFT/PRUNE at every heartbeat.
+/// At each heartbeat it also announces the messages of the last `HISTORY_GOSSIP` heartbeats
+/// as `IHAVE` hashes (`OnlyHash`) to `d` random peers out of its mesh, which pull them with
+/// `IWANT` (`AskForMessage`). A node only beats while it has something to announce or ask,
+/// so the queue empties once the messages are old.
+///
+/// With `scoring`, each node scores its peers by first deliveries, mesh peers that deliver
+/// none of the messages, and `IWANT`s they leave unanswered. Peers below 0 are pruned, never
+/// grafted, and neither gossiped to nor listened to. Scores are kept across messages. As in
+/// gossipsub, a peer that always gets messages from this node never delivers any back, so
+/// with a fixed source honest peers downstream get pruned too.
+#[derive(Debug, Clone)]
+pub struct Gossipsub {
+    d: usize,
+    d_lo: usize,
+    d_hi: usize,
+    heartbeat: u32,
+    scoring: bool,
+    mesh: Vec<BTreeSet<NodeId>>,
+    /// messages each node received and still announces, with heartbeats left.
+    mcache: Vec<Vec<(MessageId, u32)>>,
+    /// `IWANT`s each node sent and waits for: message, peer asked, ts.
+    promises: Vec<Vec<(MessageId, NodeId, TimeStamp)>>,
+    /// nodes whose heartbeat timer is set.
+    beating: Vec<bool>,
+    /// what each node saw of each peer, by `(node, peer)`.
+    peer_stats: HashMap<(NodeId, NodeId), PeerStats>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PeerStats {
+    first_deliveries: u32,
+    /// messages received while the peer was in the mesh, and copies it delivered of them.
+    mesh_messages: u32,
+    mesh_deliveries: u32,
+    /// left over from being pruned for delivering nothing.
+    mesh_failure_penalty: f64,
+    broken_promises: u32,
+}
+
+impl PeerStats {
+    fn mesh_deficit(&self) -> f64 {
+        if self.mesh_messages >= MESH_ACTIVATION && self.mesh_deliveries == 0 {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn score(&self) -> f64 {
+        self.first_deliveries.min(FIRST_DELIVERY_CAP) as f64
+            - self.mesh_deficit().powi(2)
+            - self.mesh_failure_penalty
+            - (self.broken_promises as f64).powi(2)
+    }
+}
+
+impl Gossipsub {
+    pub fn new(d: u32, d_lo: u32, d_hi: u32, heartbeat: u32, scoring: bool) -> Gossipsub {
+        Gossipsub {
+            d: d as usize,
+            d_lo: d_lo as usize,
+            d_hi: d_hi as usize,
+            heartbeat,
+            scoring,
+            mesh: Vec::new(),
+            mcache: Vec::new(),
+            promises: Vec::new(),
+            beating: Vec::new(),
+            peer_stats: HashMap::new(),
+        }
+    }
+
+    pub fn mesh(&self, node: NodeId) -> &BTreeSet<NodeId> {
+        &self.mesh[node]
+    }
+
+    /// score `node` gives `peer`, 0 without scoring.
+    pub fn score(&self, node: NodeId, peer: NodeId) -> f64 {
+        if !self.scoring {
+            return 0.0;
+        }
+        self.peer_stats
+            .get(&(node, peer))
+            .map_or(0.0, |stats| stats.score())
+    }
+
+    fn stats_mut(&mut self, node: NodeId, peer: NodeId) -> &mut PeerStats {
+        self.peer_stats.entry((node, peer)).or_default()
+    }
+
+    fn graft(&mut self, node: NodeId, peer: NodeId) {
+        self.mesh[node].insert(peer);
+    }
+
+    fn prune(&mut self, node: NodeId, peer: NodeId) {
+        self.mesh[node].remove(&peer);
+        let stats = self.stats_mut(node, peer);
+        stats.mesh_failure_penalty += stats.mesh_deficit().powi(2);
+        stats.mesh_messages = 0;
+        stats.mesh_deliveries = 0;
+    }
+
+    fn arm_heartbeat(&mut self, net: &mut Network, node: NodeId, ts: TimeStamp) {
+        if self.beating[node] {
+            return;
+        }
+        self.beating[node] = true;
+        let timer = Timer {
+            node,
+            message_id: 0,
+            tag: 0,
+        };
+        net.set_timer(timer, ts + self.heartbeat);
+    }
+
+    /// up to `max_num` random neighbours of `node` out of its mesh that it scores at least 0.
+    fn pick_peers(&self, net: &mut Network, node: NodeId, max_num: usize) -> Vec<NodeId> {
+        let candidates: Vec<NodeId> = net
+            .topology()
+            .neighbours(node)
+            .into_iter()
+            .filter(|peer| !self.mesh[node].contains(peer) && self.score(node, *peer) >= 0.0)
+            .collect();
+        candidates
+            .choose_multiple(net.rng(), max_num)
+            .cloned()
+            .collect()
+    }
+
+    fn on_message(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, from, message_id) = (message.to, message.from, message.message_id);
+        let behaviour = net.behaviours().of(node);
+        let status = net.node_status_mut(message_id, node);
+        status.record_recv_message(ts);
+        let first = status.recv_message_count() == 1;
+        if behaviour == Behaviour::Dropper {
+            net.behaviours_mut().record_swallowed(message_id);
+            return;
+        }
+        // the source gets it from itself
+        if from != node {
+            if self.mesh[node].contains(&from) {
+                self.stats_mut(node, from).mesh_deliveries += 1;
+            }
+            if first {
+                self.stats_mut(node, from).first_deliveries += 1;
+            }
+        }
+        let spam = behaviour == Behaviour::Spammer
+            && message.hop_num < net.params().max_hop_num()
+            && net.node_status_mut(message_id, node).spend_spam_budget();
+        if !first && !spam {
+            return;
+        }
+        if first {
+            if from != node {
+                for peer in self.mesh[node].clone() {
+                    self.stats_mut(node, peer).mesh_messages += 1;
+                }
+            }
+            self.mcache[node].push((message_id, HISTORY_GOSSIP));
+            self.arm_heartbeat(net, node, ts);
+        }
+        let hop_num = message.hop_num + 1;
+        for dst in self.mesh[node].clone() {
+            if dst != from {
+                send(
+                    net,
+                    node,
+                    dst,
+                    message_id,
+                    MessageStatus::FullMessage,
+                    hop_num,
+                    ts,
+                );
+            }
+        }
+    }
+
+    fn on_ihave(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, from, message_id) = (message.to, message.from, message.message_id);
+        net.node_status_mut(message_id, node).record_recv_hash();
+        if net.behaviours().of(node) == Behaviour::Dropper {
+            net.behaviours_mut().record_swallowed(message_id);
+            return;
+        }
+        let asker = &net.node_status(message_id)[node];
+        if asker.has_recv_full_message()
+            || self.score(node, from) < 0.0
+            || (asker.send_ask_for_ts() != 0
+                && asker.send_ask_for_ts() + net.params().send_ask_interval() >= ts)
+        {
+            return;
+        }
+        net.node_status_mut(message_id, node)
+            .record_send_ask_for(ts);
+        self.promises[node].push((message_id, from, ts));
+        self.arm_heartbeat(net, node, ts);
+        send(
+            net,
+            node,
+            from,
+            message_id,
+            MessageStatus::AskForMessage,
+            0,
+            ts,
+        );
+    }
+
+    fn on_iwant(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, message_id) = (message.to, message.message_id);
+        if !net.node_status(message_id)[node].has_recv_full_message() {
+            return;
+        }
+        if !net.behaviours().of(node).replies_ask_for() {
+            log::debug!("  {} ignores iwant", node);
+            net.behaviours_mut().record_unanswered_ask_for(message_id);
+            return;
+        }
+        send(
+            net,
+            node,
+            message.from,
+            message_id,
+            MessageStatus::FullMessage,
+            0,
+            ts,
+        );
+    }
+
+    fn on_graft(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, from, message_id) = (message.to, message.from, message.message_id);
+        if net.behaviours().of(node) == Behaviour::Dropper {
+            net.behaviours_mut().record_swallowed(message_id);
+            return;
+        }
+        if self.score(node, from) < 0.0 {
+            send(net, node, from, message_id, MessageStatus::Prune, 0, ts);
+            return;
+        }
+        self.graft(node, from);
+    }
+
+    /// settle promises, keep the mesh within `d_lo..=d_hi` and announce recent messages.
+    fn heartbeat(&mut self, net: &mut Network, node: NodeId, ts: TimeStamp) {
+        // mesh upkeep is counted on the message that keeps the node beating
+        let upkeep_id = self.mcache[node]
+            .first()
+            .map(|(id, _)| *id)
+            .or_else(|| self.promises[node].first().map(|(id, _, _)| *id))
+            .unwrap_or(0);
+
+        // an `IWANT` is kept if the message came from anyone within a heartbeat
+        let heartbeat = self.heartbeat;
+        let (due, pending): (Vec<_>, Vec<_>) = self.promises[node]
+            .drain(..)
+            .partition(|(_, _, asked_ts)| asked_ts + heartbeat <= ts);
+        self.promises[node] = pending;
+        for (message_id, peer, _) in due {
+            if !net.node_status(message_id)[node].has_recv_full_message() {
+                self.stats_mut(node, peer).broken_promises += 1;
+            }
+        }
+
+        for peer in self.mesh[node].clone() {
+            if self.score(node, peer) < 0.0 {
+                self.prune(node, peer);
+                send(net, node, peer, upkeep_id, MessageStatus::Prune, 0, ts);
+            }
+        }
+        if self.mesh[node].len() < self.d_lo {
+            let want = self.d - self.mesh[node].len();
+            for peer in self.pick_peers(net, node, want) {
+                self.graft(node, peer);
+                send(net, node, peer, upkeep_id, MessageStatus::Graft, 0, ts);
+            }
+        } else if self.mesh[node].len() > self.d_hi {
+            let mut peers: Vec<NodeId> = self.mesh[node].iter().cloned().collect();
+            peers.shuffle(net.rng());
+            // the best scored stay, a stable sort keeps the rest random
+            peers.sort_by(|a, b| self.score(node, *b).total_cmp(&self.score(node, *a)));
+            for peer in peers.split_off(self.d) {
+                self.prune(node, peer);
+                send(net, node, peer, upkeep_id, MessageStatus::Prune, 0, ts);
+            }
+        }
+
+        let gossip: Vec<MessageId> = self.mcache[node].iter().map(|(id, _)| *id).collect();
+        for message_id in gossip {
+            for peer in self.pick_peers(net, node, self.d) {
+                send(net, node, peer, message_id, MessageStatus::OnlyHash, 0, ts);
+            }
+        }
+        self.mcache[node].retain_mut(|(_, left)| {
+            *left -= 1;
+            *left > 0
+        });
+
+        self.beating[node] = false;
+        if !self.mcache[node].is_empty() || !self.promises[node].is_empty() {
+            self.arm_heartbeat(net, node, ts);
+        }
+    }
+}
+
+impl Protocol for Gossipsub {
+    /// every node grafts `d` random neighbours that have fewer than `d_hi` mesh peers.
+    fn start(&mut self, net: &Network) {
+        let node_size = net.node_size();
+        let mut rng = stream_rng(net.params().seed(), PROTOCOL_STREAM);
+        self.mesh = vec![BTreeSet::new(); node_size];
+        for node in 0..node_size {
+            let mut peers = net.topology().neighbours(node);
+            peers.shuffle(&mut rng);
+            for peer in peers {
+                if self.mesh[node].len() >= self.d {
+                    break;
+                }
+                if !self.mesh[node].contains(&peer) && self.mesh[peer].len() < self.d_hi {
+                    self.mesh[node].insert(peer);
+                    self.mesh[peer].insert(node);
+                }
+            }
+        }
+        self.peer_stats.clear();
+        self.reset(net);
+    }
+
+    fn reset(&mut self, net: &Network) {
+        let node_size = net.node_size();
+        self.mcache = vec![Vec::new(); node_size];
+        self.promises = vec![Vec::new(); node_size];
+        self.beating = vec![false; node_size];
+    }
+
+    fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        log::debug!("{} handle message {:?} at ts {}", message.to, message, ts);
+        match message.status {
+            MessageStatus::FullMessage => self.on_message(net, message, ts),
+            MessageStatus::OnlyHash => self.on_ihave(net, message, ts),
+            MessageStatus::AskForMessage => self.on_iwant(net, message, ts),
+            MessageStatus::Graft => self.on_graft(net, message, ts),
+            MessageStatus::Prune => {
+                if self.mesh[message.to].contains(&message.from) {
+                    self.prune(message.to, message.from);
+                }
+            }
+        }
+    }
+
+    fn on_timer(&mut self, net: &mut Network, timer: Timer, ts: TimeStamp) {
+        self.heartbeat(net, timer.node, ts);
+    }
+}
+
+#[cfg(test)]
+fn started(scoring: bool) -> (Gossipsub, Network) {
+    use crate::params_packet::ParamsPacket;
+
+    let params = ParamsPacket::builder(100, 4, 4, 1).seed(1).build().unwrap();
+    let net = Network::for_message(params, 0);
+    let mut gossipsub = Gossipsub::new(6, 5, 12, 1000, scoring);
+    gossipsub.start(&net);
+    (gossipsub, net)
+}
+
+#[test]
+fn test_gossipsub_start() {
+    let (gossipsub, _) = started(false);
+    for node in 0..100 {
+        assert!(gossipsub.mesh(node).len() <= 12);
+        for peer in gossipsub.mesh(node) {
+            assert!(gossipsub.mesh(*peer).contains(&node));
+        }
+    }
+}
+
+#[test]
+fn test_peer_score() {
+    let mut stats = PeerStats {
+        first_deliveries: 20,
+        ..Default::default()
+    };
+    assert_eq!(stats.score(), FIRST_DELIVERY_CAP as f64);
+    // a mesh peer that delivers none of `MESH_ACTIVATION` messages
+    stats = PeerStats {
+        mesh_messages: MESH_ACTIVATION,
+        ..Default::default()
+    };
+    assert_eq!(stats.score(), -1.0);
+    stats.mesh_deliveries = 1;
+    assert_eq!(stats.score(), 0.0);
+    stats.broken_promises = 2;
+    stats.mesh_failure_penalty = 1.0;
+    assert_eq!(stats.score(), -5.0);
+
+    let (mut gossipsub, _) = started(false);
+    gossipsub.stats_mut(0, 1).broken_promises = 1;
+    assert_eq!(gossipsub.score(0, 1), 0.0);
+    gossipsub.scoring = true;
+    assert_eq!(gossipsub.score(0, 1), -1.0);
+}
+
+#[test]
+fn test_gossipsub_heartbeat() {
+    use crate::message_queue::Event;
+
+    let (mut gossipsub, mut net) = started(true);
+    let node = 50;
+    let sent_of = |messages: &[Message], status| -> BTreeSet<NodeId> {
+        messages
+            .iter()
+            .filter(|m| m.status == status)
+            .map(|m| m.to)
+            .collect()
+    };
+
+    // a peer that broke its promise is pruned, the mesh grafted back up to `d` without it
+    let peers: Vec<NodeId> = gossipsub.mesh(node).iter().copied().collect();
+    gossipsub.mesh[node] = peers[..5].iter().copied().collect();
+    gossipsub.promises[node].push((0, peers[0], 0));
+    gossipsub.heartbeat(&mut net, node, 1000);
+    assert_eq!(gossipsub.score(node, peers[0]), -1.0);
+    let sent = net.drain_messages();
+    assert_eq!(
+        sent_of(&sent, MessageStatus::Prune),
+        BTreeSet::from([peers[0]])
+    );
+    assert_eq!(sent_of(&sent, MessageStatus::Graft).len(), 2);
+    assert_eq!(gossipsub.mesh(node).len(), 6);
+    assert!(!gossipsub.mesh(node).contains(&peers[0]));
+
+    // above `d_hi` it prunes back to `d`, keeping the best scored
+    gossipsub.mesh[node] = (0..node).collect();
+    gossipsub.stats_mut(node, 7).first_deliveries = 3;
+    gossipsub.heartbeat(&mut net, node, 2000);
+    assert_eq!(gossipsub.mesh(node).len(), 6);
+    assert!(gossipsub.mesh(node).contains(&7));
+    assert!(!gossipsub.mesh(node).contains(&peers[0]));
+    let sent = net.drain_messages();
+    assert_eq!(sent_of(&sent, MessageStatus::Prune).len(), node - 6);
+    assert!(sent_of(&sent, MessageStatus::Graft).is_empty());
+    // within bounds the mesh is left alone
+    gossipsub.heartbeat(&mut net, node, 3000);
+    assert!(net.drain_events().is_empty());
+
+    // a cached message is announced to `d` peers out of the mesh for `HISTORY_GOSSIP` beats
+    gossipsub.mcache[node].push((0, HISTORY_GOSSIP));
+    for beat in 1..=HISTORY_GOSSIP {
+        gossipsub.heartbeat(&mut net, node, 4000 + beat * 1000);
+        let events = net.drain_events();
+        let ihave: Vec<NodeId> = events
+            .iter()
+            .filter_map(|(event, _)| match event {
+                Event::Deliver(m) if m.status == MessageStatus::OnlyHash => Some(m.to),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ihave.len(), 6);
+        assert!(ihave
+            .iter()
+            .all(|peer| !gossipsub.mesh(node).contains(peer) && *peer != peers[0]));
+        let rearmed = events
+            .iter()
+            .any(|(event, _)| matches!(event, Event::Timer(_)));
+        assert_eq!(rearmed, beat < HISTORY_GOSSIP);
+    }
+    assert!(gossipsub.mcache[node].is_empty());
+}
+
+#[test]
+fn test_gossipsub_iwant() {
+    let (mut gossipsub, mut net) = started(true);
+    let node = 50;
+
+    // an `IHAVE` is pulled once per `send_ask_interval`, not from a peer scored below 0
+    gossipsub.on_receive(&mut net, Message::build_send_hash_message(1, node, 0), 100);
+    gossipsub.on_receive(&mut net, Message::build_send_hash_message(2, node, 0), 110);
+    let asked: Vec<Message> = net
+        .drain_messages()
+        .into_iter()
+        .filter(|m| m.status == MessageStatus::AskForMessage)
+        .collect();
+    assert_eq!(asked, vec![Message::build_send_query_message(node, 1)]);
+    gossipsub.stats_mut(node, 3).broken_promises = 1;
+    gossipsub.on_receive(
+        &mut net,
+        Message::build_send_hash_message(3, node, 0),
+        10_000,
+    );
+    assert!(net.drain_messages().is_empty());
+
+    // kept by delivery within a heartbeat
+    gossipsub.on_receive(&mut net, Message::build_send_full_message(1, node, 0), 500);
+    gossipsub.heartbeat(&mut net, node, 1100);
+    net.drain_events();
+    assert_eq!(gossipsub.score(node, 1), 1.0);
+
+    // only a node that has it answers `IWANT`
+    gossipsub.on_receive(&mut net, Message::build_send_query_message(4, node), 1200);
+    assert_eq!(
+        net.drain_messages(),
+        vec![Message::build_send_full_message(node, 4, 0)]
+    );
+    gossipsub.on_receive(&mut net, Message::build_send_query_message(node, 4), 1200);
+    assert!(net.drain_messages().is_empty());
+}
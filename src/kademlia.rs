@@ -0,0 +1,198 @@
+use std::{fmt, str::FromStr};
+
+use rand::prelude::*;
+
+use crate::{message::NodeId, sim_rng::SimRng, topology::Topology};
+
+/// 256-bit node key, most significant word first.
+pub type Key = [u64; 4];
+
+pub const KEY_BITS: usize = 256;
+
+/// How `t` broadcast targets are drawn from the k-buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KadSelection {
+    /// uniformly from all peers of the routing table.
+    Uniform,
+    /// Kadcast style: one random peer per non-empty bucket, farthest bucket first,
+    /// taking a second peer per bucket only when buckets run out.
+    PerBucket,
+}
+
+impl FromStr for KadSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(KadSelection::Uniform),
+            "per-bucket" => Ok(KadSelection::PerBucket),
+            _ => Err(format!(
+                "unknown kademlia selection `{}`, expect uniform or per-bucket",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for KadSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KadSelection::Uniform => write!(f, "uniform"),
+            KadSelection::PerBucket => write!(f, "per-bucket"),
+        }
+    }
+}
+
+/// Every node gets a random key and keeps up to `bucket_size` peers in each of its k-buckets.
+/// Bucket `i` of a node holds peers whose XOR distance to it is in `[2^i, 2^(i+1))`.
+#[derive(Debug)]
+pub struct KademliaTopology {
+    keys: Vec<Key>,
+    /// `buckets[node][i]`, only non-empty buckets are kept, farthest bucket first.
+    buckets: Vec<Vec<Vec<NodeId>>>,
+    selection: KadSelection,
+}
+
+impl KademliaTopology {
+    pub fn new(
+        node_size: usize,
+        bucket_size: usize,
+        selection: KadSelection,
+        rng: &mut SimRng,
+    ) -> KademliaTopology {
+        let keys: Vec<Key> = (0..node_size)
+            .map(|_| [rng.gen(), rng.gen(), rng.gen(), rng.gen()])
+            .collect();
+        let buckets = (0..node_size)
+            .map(|node| {
+                let mut all_buckets: Vec<Vec<NodeId>> = vec![Vec::new(); KEY_BITS];
+                for peer in (0..node_size).filter(|peer| *peer != node) {
+                    all_buckets[bucket_index(&keys[node], &keys[peer])].push(peer);
+                }
+                all_buckets
+                    .into_iter()
+                    .rev()
+                    .filter(|bucket| !bucket.is_empty())
+                    .map(|mut bucket| {
+                        bucket.shuffle(rng);
+                        bucket.truncate(bucket_size);
+                        bucket
+                    })
+                    .collect()
+            })
+            .collect();
+        KademliaTopology {
+            keys,
+            buckets,
+            selection,
+        }
+    }
+
+    pub fn key(&self, node: NodeId) -> &Key {
+        &self.keys[node]
+    }
+
+    /// non-empty k-buckets of `node`, farthest first.
+    pub fn buckets(&self, node: NodeId) -> &[Vec<NodeId>] {
+        &self.buckets[node]
+    }
+}
+
+impl Topology for KademliaTopology {
+    fn node_size(&self) -> usize {
+        self.keys.len()
+    }
+
+    fn neighbours(&self, node: NodeId) -> Vec<NodeId> {
+        self.buckets[node].iter().flatten().cloned().collect()
+    }
+
+    fn degree(&self, node: NodeId) -> usize {
+        self.buckets[node].iter().map(|bucket| bucket.len()).sum()
+    }
+
+    fn random_neighbours(&self, node: NodeId, max_num: usize, rng: &mut SimRng) -> Vec<NodeId> {
+        match self.selection {
+            KadSelection::Uniform => {
+                let mut neighbours = self.neighbours(node);
+                neighbours.shuffle(rng);
+                neighbours.truncate(max_num);
+                neighbours
+            }
+            KadSelection::PerBucket => {
+                let mut shuffled: Vec<Vec<NodeId>> = self.buckets[node]
+                    .iter()
+                    .map(|bucket| {
+                        let mut bucket = bucket.clone();
+                        bucket.shuffle(rng);
+                        bucket
+                    })
+                    .collect();
+                let mut selected = Vec::with_capacity(max_num);
+                while selected.len() < max_num && shuffled.iter().any(|b| !b.is_empty()) {
+                    for bucket in shuffled.iter_mut() {
+                        if selected.len() == max_num {
+                            break;
+                        }
+                        if let Some(peer) = bucket.pop() {
+                            selected.push(peer);
+                        }
+                    }
+                }
+                selected
+            }
+        }
+    }
+}
+
+/// index of the highest differing bit of the two keys, i.e. `floor(log2(a XOR b))`.
+pub fn bucket_index(a: &Key, b: &Key) -> usize {
+    for (word, (x, y)) in a.iter().zip(b.iter()).enumerate() {
+        let diff = x ^ y;
+        if diff != 0 {
+            return KEY_BITS - 1 - (word * 64 + diff.leading_zeros() as usize);
+        }
+    }
+    // same key, only possible for the node itself
+    0
+}
+
+#[test]
+fn test_bucket_index() {
+    assert_eq!(bucket_index(&[0, 0, 0, 0], &[0, 0, 0, 1]), 0);
+    assert_eq!(bucket_index(&[0, 0, 0, 0], &[0, 0, 0, 0b110]), 2);
+    assert_eq!(bucket_index(&[1 << 63, 0, 0, 0], &[0, 0, 0, 0]), 255);
+    assert_eq!(bucket_index(&[0, 1, 0, 5], &[0, 0, 0, 5]), 128);
+}
+
+#[test]
+fn test_kademlia_routing_table() {
+    let mut rng = crate::sim_rng::new_rng(0);
+    let kad = KademliaTopology::new(500, 4, KadSelection::PerBucket, &mut rng);
+    for node in 0..500 {
+        let buckets = kad.buckets(node);
+        // about log2(500) non-empty buckets
+        assert!((5..=20).contains(&buckets.len()));
+        let mut last_index = KEY_BITS;
+        for bucket in buckets {
+            assert!(!bucket.is_empty() && bucket.len() <= 4);
+            let index = bucket_index(kad.key(node), kad.key(bucket[0]));
+            assert!(index < last_index);
+            assert!(bucket
+                .iter()
+                .all(|peer| bucket_index(kad.key(node), kad.key(*peer)) == index));
+            last_index = index;
+        }
+        // farthest bucket holds about half of the network, truncated to bucket size
+        assert_eq!(buckets[0].len(), 4);
+    }
+
+    // per bucket selection takes one peer from each of the farthest buckets first
+    let selected = kad.random_neighbours(0, 3, &mut rng);
+    assert_eq!(selected.len(), 3);
+    for (bucket, peer) in kad.buckets(0).iter().zip(selected.iter()) {
+        assert!(bucket.contains(peer));
+    }
+    let all = kad.random_neighbours(0, 1000, &mut rng);
+    assert_eq!(all.len(), kad.degree(0));
+}
@@ -0,0 +1,377 @@
+use std::{fmt, fs, str::FromStr, sync::Arc};
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::{message::NodeId, params_packet::ParamsPacket, sim_rng::SimRng};
+
+/// what is being sent, uniform latency model gives each its own delay range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayKind {
+    Message,
+    Hash,
+    AskFor,
+    ReplyAsk,
+}
+
+/// How long a message takes from `from` to `to`. Built once per run.
+pub trait LatencyModel: fmt::Debug {
+    fn delay(&self, from: NodeId, to: NodeId, kind: DelayKind, rng: &mut SimRng) -> u32;
+
+    /// index of the region `node` is placed in, `None` if the model has no regions.
+    fn region_of(&self, _node: NodeId) -> Option<usize> {
+        None
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub enum LatencyKind {
+    /// uniform delay in the `send_*_delay_range` of params, regardless of who talks to whom.
+    #[default]
+    Uniform,
+    /// per pair one-way latency read from a matrix file.
+    Matrix(Arc<LatencyMatrix>),
+    /// nodes placed in geographic regions with an inter-region RTT table.
+    Regions(Arc<RegionTable>),
+}
+
+impl LatencyKind {
+    pub fn validate(&self, node_size: u32) -> Result<(), String> {
+        match self {
+            LatencyKind::Uniform => Ok(()),
+            LatencyKind::Matrix(matrix) => {
+                if matrix.latency.len() < node_size as usize {
+                    Err(format!(
+                        "latency matrix {} has {} nodes, less than node_size {}",
+                        matrix.source,
+                        matrix.latency.len(),
+                        node_size
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            LatencyKind::Regions(_) => Ok(()),
+        }
+    }
+
+    /// `Err` if no node can be placed in a region.
+    pub fn build(
+        &self,
+        params: &ParamsPacket,
+        rng: &mut SimRng,
+    ) -> Result<Box<dyn LatencyModel>, String> {
+        Ok(match self {
+            LatencyKind::Uniform => Box::new(UniformLatency {
+                send_message_delay_range: params.send_message_delay_range(),
+                send_hash_delay_range: params.send_hash_delay_range(),
+                send_ask_for_delay_range: params.send_ask_for_delay_range(),
+                send_reply_ask_delay_range: params.send_reply_ask_delay_range(),
+            }),
+            LatencyKind::Matrix(matrix) => Box::new(MatrixLatency {
+                matrix: matrix.clone(),
+            }),
+            LatencyKind::Regions(table) => Box::new(RegionLatency {
+                node_region: table.assign(params.node_size() as usize, rng)?,
+                table: table.clone(),
+            }),
+        })
+    }
+}
+
+/// `uniform`, `matrix:<file>` or `regions:<file>`; files are loaded while parsing.
+impl FromStr for LatencyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "uniform" => Ok(LatencyKind::Uniform),
+            Some(("matrix", path)) => Ok(LatencyKind::Matrix(Arc::new(LatencyMatrix::from_file(
+                path,
+            )?))),
+            Some(("regions", path)) => Ok(LatencyKind::Regions(Arc::new(RegionTable::from_file(
+                path,
+            )?))),
+            _ => Err(format!(
+                "unknown latency model `{}`, expect uniform, matrix:<file> or regions:<file>",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for LatencyKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for LatencyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LatencyKind::Uniform => write!(f, "uniform"),
+            LatencyKind::Matrix(matrix) => write!(f, "matrix:{}", matrix.source),
+            LatencyKind::Regions(table) => write!(f, "regions:{}", table.source),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UniformLatency {
+    send_message_delay_range: (u32, u32),
+    send_hash_delay_range: (u32, u32),
+    send_ask_for_delay_range: (u32, u32),
+    send_reply_ask_delay_range: (u32, u32),
+}
+
+impl LatencyModel for UniformLatency {
+    fn delay(&self, _from: NodeId, _to: NodeId, kind: DelayKind, rng: &mut SimRng) -> u32 {
+        let range = match kind {
+            DelayKind::Message => self.send_message_delay_range,
+            DelayKind::Hash => self.send_hash_delay_range,
+            DelayKind::AskFor => self.send_ask_for_delay_range,
+            DelayKind::ReplyAsk => self.send_reply_ask_delay_range,
+        };
+        random_delay(0, range, rng)
+    }
+}
+
+/// One-way latency between every pair of nodes, plus uniform jitter.
+///
+/// File format: optional `jitter <ms>` line, then one row of whitespace or comma separated
+/// latencies (ms) per node; `#` starts a comment. Row `i` column `j` is the latency from `i` to `j`.
+#[derive(Debug, PartialEq)]
+pub struct LatencyMatrix {
+    source: String,
+    latency: Vec<Vec<u32>>,
+    jitter: u32,
+}
+
+impl LatencyMatrix {
+    pub fn from_file(path: &str) -> Result<LatencyMatrix, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        LatencyMatrix::parse(path, &content)
+    }
+
+    pub fn parse(source: &str, content: &str) -> Result<LatencyMatrix, String> {
+        let mut jitter = 0;
+        let mut latency = Vec::new();
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("jitter") {
+                jitter = value
+                    .trim()
+                    .parse()
+                    .map_err(|e| format!("{}: invalid jitter: {}", source, e))?;
+                continue;
+            }
+            let row = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .map_err(|e| format!("{}: invalid latency: {}", source, e))?;
+            latency.push(row);
+        }
+        if latency.iter().any(|row| row.len() != latency.len()) {
+            return Err(format!("{}: latency matrix must be square", source));
+        }
+        Ok(LatencyMatrix {
+            source: source.to_string(),
+            latency,
+            jitter,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MatrixLatency {
+    matrix: Arc<LatencyMatrix>,
+}
+
+impl LatencyModel for MatrixLatency {
+    fn delay(&self, from: NodeId, to: NodeId, _kind: DelayKind, rng: &mut SimRng) -> u32 {
+        self.matrix.latency[from][to] + random_delay(0, (0, self.matrix.jitter + 1), rng)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Region {
+    pub name: String,
+    /// relative share of nodes placed in this region.
+    pub weight: f64,
+}
+
+/// Regions with their node shares and the RTT (ms) between every two regions, read from toml:
+///
+/// ```toml
+/// jitter = 10
+/// regions = [{ name = "eu", weight = 0.5 }, { name = "us", weight = 0.5 }]
+/// rtt = [[20, 90], [90, 20]]
+/// ```
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct RegionTable {
+    #[serde(skip)]
+    source: String,
+    #[serde(default)]
+    jitter: u32,
+    regions: Vec<Region>,
+    rtt: Vec<Vec<u32>>,
+}
+
+impl RegionTable {
+    pub fn from_file(path: &str) -> Result<RegionTable, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        RegionTable::parse(path, &content)
+    }
+
+    pub fn parse(source: &str, content: &str) -> Result<RegionTable, String> {
+        let mut table: RegionTable =
+            toml::from_str(content).map_err(|e| format!("{}: {}", source, e))?;
+        table.source = source.to_string();
+        if table.regions.is_empty() {
+            return Err(format!("{}: at least one region is needed", source));
+        }
+        if table
+            .regions
+            .iter()
+            .any(|r| !r.weight.is_finite() || r.weight < 0.0)
+            || table.regions.iter().all(|r| r.weight == 0.0)
+        {
+            return Err(format!(
+                "{}: region weights must be finite, >= 0 and not all 0",
+                source
+            ));
+        }
+        if table.rtt.len() != table.regions.len()
+            || table.rtt.iter().any(|row| row.len() != table.regions.len())
+        {
+            return Err(format!(
+                "{}: rtt must be a {} x {} table",
+                source,
+                table.regions.len(),
+                table.regions.len()
+            ));
+        }
+        Ok(table)
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// place every node in a region, drawn by region weight.
+    pub fn assign(&self, node_size: usize, rng: &mut SimRng) -> Result<Vec<usize>, String> {
+        let weights: Vec<f64> = self.regions.iter().map(|r| r.weight).collect();
+        let dist = rand::distributions::WeightedIndex::new(&weights)
+            .map_err(|e| format!("{}: region weights: {}", self.source, e))?;
+        Ok((0..node_size).map(|_| dist.sample(rng)).collect())
+    }
+}
+
+#[derive(Debug)]
+pub struct RegionLatency {
+    table: Arc<RegionTable>,
+    node_region: Vec<usize>,
+}
+
+impl LatencyModel for RegionLatency {
+    fn delay(&self, from: NodeId, to: NodeId, _kind: DelayKind, rng: &mut SimRng) -> u32 {
+        let rtt = self.table.rtt[self.node_region[from]][self.node_region[to]];
+        rtt / 2 + random_delay(0, (0, self.table.jitter + 1), rng)
+    }
+
+    fn region_of(&self, node: NodeId) -> Option<usize> {
+        Some(self.node_region[node])
+    }
+}
+
+pub fn random_delay(ori: u32, rg: (u32, u32), rng: &mut SimRng) -> u32 {
+    ori + rng.gen_range(rg.0..rg.1)
+}
+
+#[test]
+fn test_random_delay() {
+    let range: (u32, u32) = (1, 10);
+    let mut rng = crate::sim_rng::new_rng(0);
+    for _ in 0..100 {
+        let delay = random_delay(10, range, &mut rng);
+        assert!((11..20).contains(&delay));
+    }
+}
+
+#[test]
+fn test_parse_latency_matrix() {
+    let matrix = LatencyMatrix::parse(
+        "test",
+        "# 3 nodes\njitter 5\n0, 10, 20\n10 0 30\n20,30,0 # last row\n",
+    )
+    .unwrap();
+    assert_eq!(matrix.jitter, 5);
+    assert_eq!(matrix.latency[1], vec![10, 0, 30]);
+
+    let model = MatrixLatency {
+        matrix: Arc::new(matrix),
+    };
+    let mut rng = crate::sim_rng::new_rng(0);
+    for _ in 0..50 {
+        let delay = model.delay(0, 2, DelayKind::Message, &mut rng);
+        assert!((20..=25).contains(&delay));
+    }
+
+    assert!(LatencyMatrix::parse("test", "0 1\n1 0 3\n").is_err());
+    assert!(LatencyKind::Matrix(Arc::new(
+        LatencyMatrix::parse("test", "0 1\n1 0\n").unwrap()
+    ))
+    .validate(3)
+    .is_err());
+}
+
+#[test]
+fn test_region_latency() {
+    let table = RegionTable::parse(
+        "test",
+        r#"
+        regions = [{ name = "eu", weight = 1.0 }, { name = "asia", weight = 1.0 }]
+        rtt = [[20, 200], [200, 20]]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(table.regions().len(), 2);
+
+    let mut rng = crate::sim_rng::new_rng(0);
+    let model = RegionLatency {
+        node_region: table.assign(100, &mut rng).unwrap(),
+        table: Arc::new(table),
+    };
+    let far = (1..100)
+        .find(|n| model.region_of(*n) != model.region_of(0))
+        .unwrap();
+    let near = (1..100)
+        .find(|n| model.region_of(*n) == model.region_of(0))
+        .unwrap();
+    assert_eq!(model.delay(0, far, DelayKind::Hash, &mut rng), 100);
+    assert_eq!(model.delay(0, near, DelayKind::Hash, &mut rng), 10);
+
+    assert!(RegionTable::parse(
+        "test",
+        "regions = [{ name = \"eu\", weight = 1.0 }]\nrtt = [[1, 2]]"
+    )
+    .is_err());
+    for weight in ["nan", "inf", "-1.0"] {
+        assert!(RegionTable::parse(
+            "test",
+            &format!(
+                "regions = [{{ name = \"eu\", weight = {} }}]\nrtt = [[1]]",
+                weight
+            )
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,56 @@
+#[allow(unused)]
+pub mod bandwidth;
+#[allow(unused)]
+pub mod baseline;
+#[allow(unused)]
+pub mod behaviour;
+#[allow(unused)]
+pub mod bloom;
+#[allow(unused)]
+pub mod erasure;
+#[allow(unused)]
+pub mod failure;
+#[allow(unused)]
+pub mod gossipsub;
+#[allow(unused)]
+pub mod kademlia;
+#[allow(unused)]
+pub mod latency;
+#[allow(unused)]
+pub mod loss;
+#[allow(unused)]
+pub mod message;
+#[allow(unused)]
+pub mod message_queue;
+#[allow(unused)]
+pub mod network;
+#[allow(unused)]
+pub mod node_status;
+#[allow(unused)]
+pub mod output;
+#[allow(unused)]
+pub mod params_packet;
+#[allow(unused)]
+pub mod partition;
+#[allow(unused)]
+pub mod performance_result;
+#[allow(unused)]
+pub mod plumtree;
+#[allow(unused)]
+pub mod protocol;
+#[allow(unused)]
+pub mod rrs;
+#[allow(unused)]
+pub mod rrs_simulator;
+#[allow(unused)]
+pub mod sim_rng;
+#[allow(unused)]
+pub mod source;
+#[allow(unused)]
+pub mod statistics;
+#[allow(unused)]
+pub mod sweep;
+#[allow(unused)]
+pub mod topology;
+#[allow(unused)]
+pub mod traffic;
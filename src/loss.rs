@@ -0,0 +1,273 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    message::{counter_of, Message, MessageId, MessageStatus, NodeId},
+    sim_rng::{stream_rng, SimRng, LOSS_STREAM},
+};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub enum LossKind {
+    /// every sent message is delivered.
+    #[default]
+    None,
+    /// each send is lost with probability `p`, independently.
+    Bernoulli { p: f64 },
+    /// bursty loss: every link is in a good or bad state, moving good -> bad with probability
+    /// `p` and bad -> good with `r` before each send, losing with `loss_good`/`loss_bad`.
+    GilbertElliott {
+        p: f64,
+        r: f64,
+        loss_good: f64,
+        loss_bad: f64,
+    },
+}
+
+impl LossKind {
+    pub fn validate(&self) -> Result<(), String> {
+        let probabilities: Vec<f64> = match *self {
+            LossKind::None => vec![],
+            LossKind::Bernoulli { p } => vec![p],
+            LossKind::GilbertElliott {
+                p,
+                r,
+                loss_good,
+                loss_bad,
+            } => {
+                if p + r == 0.0 {
+                    return Err(format!("{}: p and r must not both be 0", self));
+                }
+                vec![p, r, loss_good, loss_bad]
+            }
+        };
+        if probabilities.iter().all(|p| (0.0..=1.0).contains(p)) {
+            Ok(())
+        } else {
+            Err(format!("{}: probabilities must be in [0, 1]", self))
+        }
+    }
+
+    /// long run fraction of lost sends.
+    pub fn mean_loss_rate(&self) -> f64 {
+        match *self {
+            LossKind::None => 0.0,
+            LossKind::Bernoulli { p } => p,
+            LossKind::GilbertElliott {
+                p,
+                r,
+                loss_good,
+                loss_bad,
+            } => (r * loss_good + p * loss_bad) / (p + r),
+        }
+    }
+}
+
+/// `none`, `bernoulli:<p>` or `ge:<p>:<r>[:<loss_bad>[:<loss_good>]]`
+/// (`loss_bad` defaults to 1, `loss_good` to 0).
+impl FromStr for LossKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let parse = |v: &str| v.parse::<f64>().map_err(|e| format!("{} in `{}`", e, s));
+        match parts.as_slice() {
+            ["none"] => Ok(LossKind::None),
+            ["bernoulli", p] => Ok(LossKind::Bernoulli { p: parse(p)? }),
+            ["ge", p, r, rest @ ..] if rest.len() <= 2 => Ok(LossKind::GilbertElliott {
+                p: parse(p)?,
+                r: parse(r)?,
+                loss_bad: rest.first().map_or(Ok(1.0), |v| parse(v))?,
+                loss_good: rest.get(1).map_or(Ok(0.0), |v| parse(v))?,
+            }),
+            _ => Err(format!(
+                "unknown packet loss `{}`, expect none, bernoulli:<p> or ge:<p>:<r>[:<loss_bad>[:<loss_good>]]",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for LossKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for LossKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LossKind::None => write!(f, "none"),
+            LossKind::Bernoulli { p } => write!(f, "bernoulli:{}", p),
+            LossKind::GilbertElliott {
+                p,
+                r,
+                loss_good,
+                loss_bad,
+            } => write!(f, "ge:{}:{}:{}:{}", p, r, loss_bad, loss_good),
+        }
+    }
+}
+
+/// Decide for each send whether it is lost.
+#[derive(Debug, Clone)]
+pub struct PacketLoss {
+    kind: LossKind,
+    /// links in the bad state, gilbert-elliott only. Links start in the good state.
+    bad_links: HashMap<(NodeId, NodeId), bool>,
+    /// lost sends of each message id.
+    lost_message_count: Vec<u32>,
+    lost_hash_count: Vec<u32>,
+    lost_ask_for_count: Vec<u32>,
+    /// lost grafts and prunes.
+    lost_control_count: Vec<u32>,
+    rng: SimRng,
+}
+
+// getter
+impl PacketLoss {
+    pub fn lost_message_count(&self, message_id: MessageId) -> u32 {
+        self.lost_message_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
+    }
+    pub fn lost_hash_count(&self, message_id: MessageId) -> u32 {
+        self.lost_hash_count.get(message_id).copied().unwrap_or(0)
+    }
+    pub fn lost_ask_for_count(&self, message_id: MessageId) -> u32 {
+        self.lost_ask_for_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
+    }
+    pub fn lost_control_count(&self, message_id: MessageId) -> u32 {
+        self.lost_control_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl PacketLoss {
+    pub fn new(kind: LossKind) -> PacketLoss {
+        PacketLoss {
+            kind,
+            bad_links: HashMap::new(),
+            lost_message_count: Vec::new(),
+            lost_hash_count: Vec::new(),
+            lost_ask_for_count: Vec::new(),
+            lost_control_count: Vec::new(),
+            rng: stream_rng(0, LOSS_STREAM),
+        }
+    }
+
+    pub fn reset(&mut self, seed: u64) {
+        self.rng = stream_rng(seed, LOSS_STREAM);
+        self.bad_links.clear();
+        self.lost_message_count.clear();
+        self.lost_hash_count.clear();
+        self.lost_ask_for_count.clear();
+        self.lost_control_count.clear();
+    }
+
+    pub fn record_lost(&mut self, message: &Message) {
+        let counters = match message.status {
+            MessageStatus::FullMessage => &mut self.lost_message_count,
+            MessageStatus::OnlyHash => &mut self.lost_hash_count,
+            MessageStatus::AskForMessage => &mut self.lost_ask_for_count,
+            MessageStatus::Graft | MessageStatus::Prune => &mut self.lost_control_count,
+        };
+        *counter_of(counters, message.message_id) += 1;
+    }
+
+    /// if a message sent now from `from` to `to` is lost.
+    pub fn is_lost(&mut self, from: NodeId, to: NodeId) -> bool {
+        match self.kind {
+            LossKind::None => false,
+            LossKind::Bernoulli { p } => self.rng.gen_bool(p),
+            LossKind::GilbertElliott {
+                p,
+                r,
+                loss_good,
+                loss_bad,
+            } => {
+                let bad = self.bad_links.entry((from, to)).or_insert(false);
+                *bad = if *bad {
+                    !self.rng.gen_bool(r)
+                } else {
+                    self.rng.gen_bool(p)
+                };
+                let loss = if *bad { loss_bad } else { loss_good };
+                self.rng.gen_bool(loss)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_loss_kind() {
+    assert_eq!("none".parse(), Ok(LossKind::None));
+    assert_eq!(
+        "bernoulli:0.05".parse(),
+        Ok(LossKind::Bernoulli { p: 0.05 })
+    );
+    assert_eq!(
+        "ge:0.01:0.3".parse(),
+        Ok(LossKind::GilbertElliott {
+            p: 0.01,
+            r: 0.3,
+            loss_good: 0.0,
+            loss_bad: 1.0
+        })
+    );
+    for s in ["none", "bernoulli:0.1", "ge:0.01:0.3:0.8:0.001"] {
+        assert_eq!(s.parse::<LossKind>().unwrap().to_string(), s);
+    }
+    assert!("ge:0.1".parse::<LossKind>().is_err());
+    assert!(LossKind::Bernoulli { p: 1.5 }.validate().is_err());
+    assert!("ge:0:0".parse::<LossKind>().unwrap().validate().is_err());
+}
+
+#[test]
+fn test_loss_rate() {
+    for kind in [
+        LossKind::Bernoulli { p: 0.1 },
+        LossKind::GilbertElliott {
+            p: 0.02,
+            r: 0.2,
+            loss_good: 0.0,
+            loss_bad: 1.0,
+        },
+    ] {
+        let mut loss = PacketLoss::new(kind);
+        loss.reset(3);
+        let sends = 50_000;
+        let lost = (0..sends).filter(|i| loss.is_lost(0, i % 5)).count();
+        let rate = lost as f64 / sends as f64;
+        assert!(
+            (rate - kind.mean_loss_rate()).abs() < 0.015,
+            "{}: {}",
+            kind,
+            rate
+        );
+    }
+
+    // losses come in bursts on a gilbert-elliott link
+    let mut loss = PacketLoss::new(LossKind::GilbertElliott {
+        p: 0.01,
+        r: 0.1,
+        loss_good: 0.0,
+        loss_bad: 1.0,
+    });
+    loss.reset(4);
+    let lost: Vec<bool> = (0..20_000).map(|_| loss.is_lost(0, 1)).collect();
+    let bursts = lost.windows(2).filter(|w| !w[0] && w[1]).count();
+    let lost_count = lost.iter().filter(|l| **l).count();
+    // mean burst length is 1 / r
+    assert!(lost_count as f64 / bursts as f64 > 5.0);
+}
@@ -1,53 +1,355 @@
-use log::{Level, LevelFilter, Metadata, Record};
-
-use crate::rrs_simulator::{ParamsPacket, RRSSimulator};
-
-#[allow(unused)]
-mod message;
-#[allow(unused)]
-mod message_queue;
-#[allow(unused)]
-mod node_status;
-#[allow(unused)]
-mod performance_result;
-#[allow(unused)]
-mod rrs_simulator;
-
-static MY_LOGGER: MyLogger = MyLogger;
-struct MyLogger;
-impl log::Log for MyLogger {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() == Level::Debug
-    }
+use std::{error::Error, path::PathBuf, process};
+
+use clap::{Args, Parser, Subcommand};
+use log::LevelFilter;
+use rrs_simulator::{
+    behaviour::BehaviourMix,
+    bloom::BloomKind,
+    latency::LatencyKind,
+    loss::LossKind,
+    output::{OutputFormat, ResultWriter},
+    params_packet::{parse_range, ParamsPacket, ParamsPacketBuilder},
+    partition::PartitionKind,
+    performance_result::ResultPack,
+    protocol::ProtocolKind,
+    rrs_simulator::RRSSimulator,
+    source::SourceKind,
+    sweep::SweepSpec,
+    topology::TopologyKind,
+};
+
+const DEFAULT_SWEEP: &str = include_str!("../sweeps/default.toml");
+
+#[derive(Parser, Debug)]
+#[clap(version, about = "Simulate RRS message broadcast")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+    /// rng seed, overrides the seed of params or sweep spec
+    #[clap(long, global = true)]
+    seed: Option<u64>,
+    /// result format: markdown, csv or jsonl
+    #[clap(long, global = true, default_value = "markdown")]
+    format: OutputFormat,
+    /// off, error, warn, info, debug or trace
+    #[clap(long, global = true, default_value = "warn")]
+    log_level: LevelFilter,
+    /// write results to this file instead of stdout (`-` for stdout)
+    #[clap(short, long, global = true)]
+    output: Option<PathBuf>,
+    /// also write one record per simulated message to this file (`-` for stdout)
+    #[clap(long, global = true)]
+    messages_output: Option<PathBuf>,
+    /// also write one record per broadcast source to this file (`-` for stdout)
+    #[clap(long, global = true)]
+    sources_output: Option<PathBuf>,
+    /// also write one record per group of each partition to this file (`-` for stdout)
+    #[clap(long, global = true)]
+    partitions_output: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// simulate one parameter set
+    Run(RunArgs),
+    /// simulate every parameter set of a sweep spec
+    Sweep(SweepArgs),
+    /// simulate parameter sets with the same seed and compare them to the first one
+    Compare(CompareArgs),
+}
+
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// network node scale
+    #[clap(short = 'N', long)]
+    node_size: u32,
+    /// neighbours selected each round
+    #[clap(short, long)]
+    t: u32,
+    /// rounds of spreading full message before switching to hash
+    #[clap(short, long)]
+    k: u32,
+    /// simulate message count
+    #[clap(short, long, default_value = "100")]
+    n: u32,
+    #[clap(flatten)]
+    protocol: ProtocolArgs,
+}
+
+#[derive(Args, Debug)]
+struct SweepArgs {
+    /// sweep spec file (.toml or .json), defaults to sweeps/default.toml
+    spec: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct CompareArgs {
+    /// network node scales, comma separated
+    #[clap(short = 'N', long, use_value_delimiter = true, required = true)]
+    node_size: Vec<u32>,
+    /// `t` values, comma separated
+    #[clap(short, long, use_value_delimiter = true, required = true)]
+    t: Vec<u32>,
+    /// `k` values, comma separated
+    #[clap(short, long, use_value_delimiter = true, required = true)]
+    k: Vec<u32>,
+    /// simulate message count
+    #[clap(short, long, default_value = "100")]
+    n: u32,
+    #[clap(flatten)]
+    protocol: ProtocolArgs,
+}
+
+#[derive(Args, Debug)]
+struct ProtocolArgs {
+    #[clap(long)]
+    max_hop_num: Option<u32>,
+    #[clap(long)]
+    each_handle_count: Option<u32>,
+    #[clap(long)]
+    send_ask_interval: Option<u32>,
+    /// bytes of the broadcast content
+    #[clap(long)]
+    payload_size: Option<u32>,
+    /// upload bandwidth of every node in kbit/s, 0 means unlimited
+    #[clap(long)]
+    upload_bandwidth: Option<u32>,
+    /// download bandwidth of every node in kbit/s, 0 means unlimited
+    #[clap(long)]
+    download_bandwidth: Option<u32>,
+    /// as `lo-hi`
+    #[clap(long, parse(try_from_str = parse_range))]
+    send_message_delay: Option<(u32, u32)>,
+    /// as `lo-hi`
+    #[clap(long, parse(try_from_str = parse_range))]
+    send_hash_delay: Option<(u32, u32)>,
+    /// as `lo-hi`
+    #[clap(long, parse(try_from_str = parse_range))]
+    send_ask_for_delay: Option<(u32, u32)>,
+    /// as `lo-hi`
+    #[clap(long, parse(try_from_str = parse_range))]
+    send_reply_ask_delay: Option<(u32, u32)>,
+    /// complete, regular:<degree>, er:<p>, ws:<k>:<beta>, ba:<m>
+    /// or kademlia:<bucket_size>[:uniform|per-bucket]
+    #[clap(long)]
+    topology: Option<TopologyKind>,
+    /// uniform (the delay ranges), matrix:<file> or regions:<file>
+    #[clap(long)]
+    latency: Option<LatencyKind>,
+    /// exact or bloom:<bits>:<hashes>
+    #[clap(long)]
+    bloom_filter: Option<BloomKind>,
+    /// fraction of nodes offline from the start, in [0, 1)
+    #[clap(long)]
+    offline_fraction: Option<f64>,
+    /// crash/join toggles per node per second
+    #[clap(long)]
+    churn_rate: Option<f64>,
+    /// none, bernoulli:<p> or ge:<p>:<r>[:<loss_bad>[:<loss_good>]]
+    #[clap(long)]
+    packet_loss: Option<LossKind>,
+    /// none, split:<groups>:<start>:<heal> or file:<path>
+    #[clap(long)]
+    partitions: Option<PartitionKind>,
+    /// honest or comma separated <behaviour>:<fraction>,
+    /// behaviour one of dropper, no-reply, bloom-liar, spammer
+    #[clap(long)]
+    behaviours: Option<BehaviourMix>,
+    /// fixed:<node>, uniform, degree:<exponent> or region:<name>
+    #[clap(long)]
+    source: Option<SourceKind>,
+    /// messages per second sent concurrently on one network, 0 means one after another
+    #[clap(long)]
+    arrival_rate: Option<f64>,
+    /// rrs, flood, push:<ttl>, plumtree[:<timeout>],
+    /// gossipsub[:<d>:<d_lo>:<d_hi>[:<heartbeat>]][:score] or erasure:<m>:<n>
+    #[clap(long)]
+    protocol: Option<ProtocolKind>,
+}
 
-    fn log(&self, record: &Record) {
-        if record.metadata().level() == Level::Debug {
-            // println!("{} - {}", record.level(), record.args());
-        } else {
-            println!("{}", record.args());
+impl ProtocolArgs {
+    fn apply(&self, mut builder: ParamsPacketBuilder) -> ParamsPacketBuilder {
+        if let Some(v) = self.max_hop_num {
+            builder = builder.max_hop_num(v);
+        }
+        if let Some(v) = self.each_handle_count {
+            builder = builder.each_handle_count(v);
+        }
+        if let Some(v) = self.send_ask_interval {
+            builder = builder.send_ask_interval(v);
+        }
+        if let Some(v) = self.payload_size {
+            builder = builder.payload_size(v);
+        }
+        if let Some(v) = self.upload_bandwidth {
+            builder = builder.upload_bandwidth(v);
+        }
+        if let Some(v) = self.download_bandwidth {
+            builder = builder.download_bandwidth(v);
+        }
+        if let Some(v) = self.send_message_delay {
+            builder = builder.send_message_delay_range(v);
+        }
+        if let Some(v) = self.send_hash_delay {
+            builder = builder.send_hash_delay_range(v);
+        }
+        if let Some(v) = self.send_ask_for_delay {
+            builder = builder.send_ask_for_delay_range(v);
+        }
+        if let Some(v) = self.send_reply_ask_delay {
+            builder = builder.send_reply_ask_delay_range(v);
+        }
+        if let Some(v) = self.topology {
+            builder = builder.topology(v);
+        }
+        if let Some(v) = &self.latency {
+            builder = builder.latency(v.clone());
+        }
+        if let Some(v) = self.bloom_filter {
+            builder = builder.bloom_filter(v);
+        }
+        if let Some(v) = self.offline_fraction {
+            builder = builder.offline_fraction(v);
+        }
+        if let Some(v) = self.churn_rate {
+            builder = builder.churn_rate(v);
+        }
+        if let Some(v) = self.packet_loss {
+            builder = builder.packet_loss(v);
         }
+        if let Some(v) = &self.partitions {
+            builder = builder.partitions(v.clone());
+        }
+        if let Some(v) = self.behaviours {
+            builder = builder.behaviours(v);
+        }
+        if let Some(v) = &self.source {
+            builder = builder.source(v.clone());
+        }
+        if let Some(v) = self.arrival_rate {
+            builder = builder.arrival_rate(v);
+        }
+        if let Some(v) = self.protocol {
+            builder = builder.protocol(v);
+        }
+        builder
+    }
+}
+
+fn build_params(
+    (node_size, t, k, n): (u32, u32, u32, u32),
+    protocol: &ProtocolArgs,
+    seed: Option<u64>,
+) -> Result<ParamsPacket, Box<dyn Error>> {
+    let mut builder = protocol.apply(ParamsPacket::builder(node_size, t, k, n));
+    if let Some(seed) = seed {
+        builder = builder.seed(seed);
     }
-    fn flush(&self) {}
+    Ok(builder.build()?)
 }
 
 fn main() {
-    log::set_logger(&MY_LOGGER).unwrap();
-    // log::set_max_level(LevelFilter::Debug);
-    log::set_max_level(LevelFilter::Info);
-
-    for node_size in (100..600).step_by(10) {
-        for t in 3..=8 as u32 {
-            for k in 2..=7 as u32 {
-                let theory_value =
-                    (f64::powi(t as f64, (k + 1) as i32) - 1.0) / (t - 1) as f64 / node_size as f64;
-                if theory_value < 0.7 || theory_value > 4.0 {
-                    continue;
+    let cli = Cli::parse();
+    env_logger::Builder::new()
+        .filter_level(cli.log_level)
+        .init();
+
+    if let Err(e) = run(&cli) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), Box<dyn Error>> {
+    let mut writer = match &cli.output {
+        Some(path) => ResultWriter::create(path, cli.format)?,
+        None => ResultWriter::stdout(cli.format),
+    };
+    let mut messages_writer = match &cli.messages_output {
+        Some(path) => Some(ResultWriter::create(path, cli.format)?),
+        None => None,
+    };
+    let mut sources_writer = match &cli.sources_output {
+        Some(path) => Some(ResultWriter::create(path, cli.format)?),
+        None => None,
+    };
+    let mut partitions_writer = match &cli.partitions_output {
+        Some(path) => Some(ResultWriter::create(path, cli.format)?),
+        None => None,
+    };
+    let mut write = |rp: &ResultPack| -> Result<(), Box<dyn Error>> {
+        writer.write(rp)?;
+        if let Some(messages_writer) = &mut messages_writer {
+            messages_writer.write_messages(rp)?;
+        }
+        if let Some(sources_writer) = &mut sources_writer {
+            sources_writer.write_sources(rp)?;
+        }
+        if let Some(partitions_writer) = &mut partitions_writer {
+            partitions_writer.write_partitions(rp)?;
+        }
+        Ok(())
+    };
+
+    match &cli.command {
+        Command::Run(args) => {
+            let params = build_params(
+                (args.node_size, args.t, args.k, args.n),
+                &args.protocol,
+                cli.seed,
+            )?;
+            write(&RRSSimulator::new(params)?.run())?;
+        }
+        Command::Sweep(args) => {
+            let mut spec = match &args.spec {
+                Some(path) => SweepSpec::from_file(path)?,
+                None => SweepSpec::from_toml(DEFAULT_SWEEP)?,
+            };
+            if cli.seed.is_some() {
+                spec.seed = cli.seed;
+            }
+            for params in spec.params_list() {
+                write(&RRSSimulator::new(params)?.run())?;
+            }
+        }
+        Command::Compare(args) => {
+            let mut results = Vec::new();
+            for &node_size in &args.node_size {
+                for &t in &args.t {
+                    for &k in &args.k {
+                        let params =
+                            build_params((node_size, t, k, args.n), &args.protocol, cli.seed)?;
+                        results.push(RRSSimulator::new(params)?.run());
+                    }
+                }
+            }
+            if let Some(messages_writer) = &mut messages_writer {
+                for rp in &results {
+                    messages_writer.write_messages(rp)?;
                 }
-                log::debug!("{} {} {} {}", node_size, t, k, theory_value);
-                let params = ParamsPacket::new(node_size, t, k, 100);
-                let mut simu = RRSSimulator::new(params);
-                simu.do_test();
             }
+            if let Some(sources_writer) = &mut sources_writer {
+                for rp in &results {
+                    sources_writer.write_sources(rp)?;
+                }
+            }
+            if let Some(partitions_writer) = &mut partitions_writer {
+                for rp in &results {
+                    partitions_writer.write_partitions(rp)?;
+                }
+            }
+            writer.write_comparison(&results)?;
         }
     }
+    writer.flush()?;
+    if let Some(messages_writer) = &mut messages_writer {
+        messages_writer.flush()?;
+    }
+    if let Some(sources_writer) = &mut sources_writer {
+        sources_writer.flush()?;
+    }
+    if let Some(partitions_writer) = &mut partitions_writer {
+        partitions_writer.flush()?;
+    }
+    Ok(())
 }
@@ -1,66 +1,226 @@
-use std::{collections::HashSet, hash::Hash};
+use std::hash::Hash;
+
+use crate::bloom::NodeFilter;
 
 pub type NodeId = usize;
+/// index of a broadcast among the messages simulated together.
+pub type MessageId = usize;
+
+/// bytes of `from`, `to`, `hop_num` and `status` on the wire.
+pub const HEADER_SIZE: u32 = 16;
+/// bytes of the message hash carried by hash headers and ask for requests.
+pub const HASH_SIZE: u32 = 32;
+/// bytes of one node id in an exact `bloomstatus`.
+pub const NODE_ID_SIZE: u32 = 4;
+/// bytes of the chunk index a coded chunk adds to the header.
+pub const CHUNK_INDEX_SIZE: u32 = 4;
 
 #[derive(Hash, PartialEq, Eq, Clone, Debug)]
 pub enum MessageStatus {
     FullMessage,
     OnlyHash,
     AskForMessage,
+    /// control: take the sender as a peer that pushes full messages, and send the message of
+    /// the hash if the receiver has it.
+    Graft,
+    /// control: stop pushing full messages to the sender.
+    Prune,
+}
+
+impl MessageStatus {
+    /// graft and prune only steer who pushes to whom.
+    pub fn is_control(&self) -> bool {
+        matches!(self, MessageStatus::Graft | MessageStatus::Prune)
+    }
+}
+
+/// bytes of a message (or of many) by what they carry.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct WireBytes {
+    /// fixed header of every message.
+    pub header: u64,
+    /// broadcast content of full messages.
+    pub payload: u64,
+    /// message hash of hash headers and ask for requests.
+    pub hash: u64,
+    /// `bloomstatus` of full messages, growing with each hop unless it is a bloom filter.
+    pub bloomstatus: u64,
+}
+
+impl WireBytes {
+    pub fn total(&self) -> u64 {
+        self.header + self.payload + self.hash + self.bloomstatus
+    }
+}
+
+impl std::ops::AddAssign for WireBytes {
+    fn add_assign(&mut self, other: WireBytes) {
+        self.header += other.header;
+        self.payload += other.payload;
+        self.hash += other.hash;
+        self.bloomstatus += other.bloomstatus;
+    }
+}
+
+/// the `index`-th coded chunk of a payload, any `m` distinct chunks of which rebuild it.
+#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Chunk {
+    pub index: u32,
+    pub m: u32,
+}
+
+impl Chunk {
+    /// bytes of the payload a chunk carries.
+    pub fn payload_size(&self, payload_size: u32) -> u32 {
+        payload_size.div_ceil(self.m)
+    }
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Message {
+    /// which broadcast this is part of, carried by its hash so it costs no extra bytes.
+    pub message_id: MessageId,
     pub from: NodeId,
     pub to: NodeId,
     pub hop_num: u32,
     pub status: MessageStatus,
-    pub bloomstatus: HashSet<NodeId>,
+    pub bloomstatus: NodeFilter,
+    /// the chunk a full message carries instead of the whole payload, if it is coded.
+    pub chunk: Option<Chunk>,
 }
 
 impl Hash for Message {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.message_id.hash(state);
         self.from.hash(state);
         self.to.hash(state);
         self.hop_num.hash(state);
         self.status.hash(state);
+        self.chunk.hash(state);
     }
 }
 
 impl Message {
     pub fn build_send_full_message(from: usize, to: usize, hop_num: u32) -> Message {
         Message {
+            message_id: 0,
             from,
             to,
             hop_num,
             status: MessageStatus::FullMessage,
-            bloomstatus: HashSet::new(),
+            bloomstatus: NodeFilter::default(),
+            chunk: None,
         }
     }
 
     pub fn build_send_hash_message(from: usize, to: usize, hop_num: u32) -> Message {
         Message {
+            message_id: 0,
             from,
             to,
             hop_num,
             status: MessageStatus::OnlyHash,
-            bloomstatus: HashSet::new(),
+            bloomstatus: NodeFilter::default(),
+            chunk: None,
+        }
+    }
+
+    pub fn build_send_control_message(from: usize, to: usize, status: MessageStatus) -> Message {
+        debug_assert!(status.is_control());
+        Message {
+            message_id: 0,
+            from,
+            to,
+            hop_num: 0,
+            status,
+            bloomstatus: NodeFilter::default(),
+            chunk: None,
         }
     }
 
     pub fn build_send_query_message(from: usize, to: usize) -> Message {
         Message {
+            message_id: 0,
             from,
             to,
             hop_num: 0,
             status: MessageStatus::AskForMessage,
-            bloomstatus: HashSet::new(),
+            bloomstatus: NodeFilter::default(),
+            chunk: None,
         }
     }
 
+    /// bytes sent on the wire: a full message carries the payload (or one chunk of it and its
+    /// index) and its `bloomstatus`, hash headers, ask for requests and grafts only the message
+    /// hash, prunes nothing.
+    pub fn wire_bytes(&self, payload_size: u32) -> WireBytes {
+        match self.status {
+            MessageStatus::FullMessage => match self.chunk {
+                None => WireBytes {
+                    header: HEADER_SIZE as u64,
+                    payload: payload_size as u64,
+                    hash: 0,
+                    bloomstatus: self.bloomstatus.wire_size(),
+                },
+                Some(chunk) => WireBytes {
+                    header: (HEADER_SIZE + CHUNK_INDEX_SIZE) as u64,
+                    payload: chunk.payload_size(payload_size) as u64,
+                    hash: 0,
+                    bloomstatus: self.bloomstatus.wire_size(),
+                },
+            },
+            MessageStatus::OnlyHash | MessageStatus::AskForMessage | MessageStatus::Graft => {
+                WireBytes {
+                    header: HEADER_SIZE as u64,
+                    payload: 0,
+                    hash: HASH_SIZE as u64,
+                    bloomstatus: 0,
+                }
+            }
+            MessageStatus::Prune => WireBytes {
+                header: HEADER_SIZE as u64,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn wire_size(&self, payload_size: u32) -> u32 {
+        self.wire_bytes(payload_size).total() as u32
+    }
+
     pub fn add_bloomstatus(&mut self, nodes: Vec<NodeId>) {
         for node_id in nodes {
             self.bloomstatus.insert(node_id);
         }
     }
 }
+
+/// counter of `message_id` in `counters`, growing it as needed.
+pub fn counter_of<T: Default + Clone>(counters: &mut Vec<T>, message_id: MessageId) -> &mut T {
+    if counters.len() <= message_id {
+        counters.resize(message_id + 1, T::default());
+    }
+    &mut counters[message_id]
+}
+
+#[test]
+fn test_wire_size() {
+    let mut message = Message::build_send_full_message(0, 1, 1);
+    assert_eq!(message.wire_size(1000), HEADER_SIZE + 1000);
+    message.add_bloomstatus(vec![2, 3, 4]);
+    assert_eq!(
+        message.wire_size(1000),
+        HEADER_SIZE + 1000 + 3 * NODE_ID_SIZE
+    );
+    assert_eq!(
+        Message::build_send_hash_message(0, 1, 1).wire_size(1000),
+        HEADER_SIZE + HASH_SIZE
+    );
+    assert_eq!(
+        Message::build_send_query_message(0, 1).wire_size(1000),
+        HEADER_SIZE + HASH_SIZE
+    );
+    let mut chunk = Message::build_send_full_message(0, 1, 1);
+    chunk.chunk = Some(Chunk { index: 2, m: 3 });
+    assert_eq!(chunk.wire_size(1000), HEADER_SIZE + CHUNK_INDEX_SIZE + 334);
+}
@@ -1,18 +1,74 @@
-use std::{cmp::Reverse, collections::HashSet};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+};
 
 use priority_queue::PriorityQueue;
 
-use crate::message::{Message, MessageStatus};
+use crate::{
+    bloom::NodeFilter,
+    message::{counter_of, Message, MessageId, MessageStatus, NodeId},
+};
 
 pub type TimeStamp = u32;
 
-/// Use one queue to simulate one message's spread process.
+/// a protocol timer set by `node` for the message of `message_id`,
+/// `tag` tells the protocol which of its timers it is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Timer {
+    pub node: NodeId,
+    pub message_id: MessageId,
+    pub tag: u32,
+}
+
+/// what happens at a ts: a message arrives at `message.to`, or a timer fires.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Deliver(Message),
+    Timer(Timer),
+}
+
+/// a queued event, told apart from identical ones by its sequence id.
+#[derive(Debug, Clone)]
+struct Queued {
+    seq: u64,
+    event: Event,
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.seq == other.seq
+    }
+}
+
+impl Eq for Queued {}
+
+impl Hash for Queued {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.seq.hash(state);
+    }
+}
+
+/// Use one queue to simulate the spread process of the messages in flight.
+/// Every push is delivered, events at the same ts in the order they were pushed.
 #[derive(Debug)]
 pub struct MessageQueue {
-    q: PriorityQueue<Message, Reverse<TimeStamp>>,
-    handled_messsage_count: u32,
-    handled_hash_count: u32,
-    handled_ask_for: u32,
+    q: PriorityQueue<Queued, Reverse<(TimeStamp, u64)>>,
+    next_seq: u64,
+    /// queued copies of each message.
+    queued: HashMap<Message, u32>,
+    /// pushed messages of each message id.
+    handled_messsage_count: Vec<u32>,
+    handled_hash_count: Vec<u32>,
+    handled_ask_for: Vec<u32>,
+    /// pushed grafts and prunes.
+    handled_control_count: Vec<u32>,
+    /// pushes of each message id while an identical message was queued, which a queue keyed
+    /// by the message alone would have merged into one delivery.
+    would_merge_count: Vec<u32>,
+    /// ts the last popped message of each message id arrived at.
+    last_deliver_ts: Vec<TimeStamp>,
 }
 
 // getter
@@ -20,15 +76,42 @@ impl MessageQueue {
     pub fn len(&self) -> usize {
         self.q.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.q.is_empty()
+    }
 
-    pub fn handled_messsage_count(&self) -> u32 {
+    pub fn handled_messsage_count(&self, message_id: MessageId) -> u32 {
         self.handled_messsage_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
     }
-    pub fn handled_hash_count(&self) -> u32 {
+    pub fn handled_hash_count(&self, message_id: MessageId) -> u32 {
         self.handled_hash_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
     }
-    pub fn handled_ask_for(&self) -> u32 {
-        self.handled_ask_for
+    pub fn handled_ask_for(&self, message_id: MessageId) -> u32 {
+        self.handled_ask_for.get(message_id).copied().unwrap_or(0)
+    }
+    pub fn handled_control_count(&self, message_id: MessageId) -> u32 {
+        self.handled_control_count
+            .get(message_id)
+            .copied()
+            .unwrap_or(0)
+    }
+    pub fn would_merge_count(&self, message_id: MessageId) -> u32 {
+        self.would_merge_count.get(message_id).copied().unwrap_or(0)
+    }
+    pub fn last_deliver_ts(&self, message_id: MessageId) -> TimeStamp {
+        self.last_deliver_ts.get(message_id).copied().unwrap_or(0)
+    }
+}
+
+impl Default for MessageQueue {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -36,35 +119,75 @@ impl MessageQueue {
     pub fn new() -> MessageQueue {
         MessageQueue {
             q: PriorityQueue::new(),
-            handled_messsage_count: 0,
-            handled_hash_count: 0,
-            handled_ask_for: 0,
+            next_seq: 0,
+            queued: HashMap::new(),
+            handled_messsage_count: Vec::new(),
+            handled_hash_count: Vec::new(),
+            handled_ask_for: Vec::new(),
+            handled_control_count: Vec::new(),
+            would_merge_count: Vec::new(),
+            last_deliver_ts: Vec::new(),
         }
     }
 
-    /// push success : return `true`
-    /// only update priority : return `false` (cause message is the same one. NEED TO AVOID) // todo
+    /// queue `message` to be delivered at `timestamp`.
+    /// no identical message queued : return `true`
+    /// an identical message queued : return `false`, counted in `would_merge_count`
     pub fn push(&mut self, message: Message, timestamp: TimeStamp) -> bool {
-        match message.status {
-            MessageStatus::FullMessage => self.handled_messsage_count += 1,
-            MessageStatus::OnlyHash => self.handled_hash_count += 1,
-            MessageStatus::AskForMessage => self.handled_ask_for += 1,
+        let counters = match message.status {
+            MessageStatus::FullMessage => &mut self.handled_messsage_count,
+            MessageStatus::OnlyHash => &mut self.handled_hash_count,
+            MessageStatus::AskForMessage => &mut self.handled_ask_for,
+            MessageStatus::Graft | MessageStatus::Prune => &mut self.handled_control_count,
+        };
+        *counter_of(counters, message.message_id) += 1;
+
+        let copies = self.queued.entry(message.clone()).or_insert(0);
+        *copies += 1;
+        let unique = *copies == 1;
+        if !unique {
+            *counter_of(&mut self.would_merge_count, message.message_id) += 1;
         }
-        self.q.push(message, Reverse(timestamp)).is_none()
+        self.push_event(Event::Deliver(message), timestamp);
+        unique
     }
 
-    pub fn pop_front(&mut self) -> Option<(Message, TimeStamp)> {
-        match self.q.pop() {
-            None => None,
-            Some((m, ts)) => Some((m, ts.0)),
+    /// fire `timer` at `timestamp`.
+    pub fn push_timer(&mut self, timer: Timer, timestamp: TimeStamp) {
+        self.push_event(Event::Timer(timer), timestamp);
+    }
+
+    fn push_event(&mut self, event: Event, timestamp: TimeStamp) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.q
+            .push(Queued { seq, event }, Reverse((timestamp, seq)));
+    }
+
+    pub fn pop_front(&mut self) -> Option<(Event, TimeStamp)> {
+        let (Queued { event, .. }, Reverse((ts, _))) = self.q.pop()?;
+        if let Event::Deliver(message) = &event {
+            *counter_of(&mut self.last_deliver_ts, message.message_id) = ts;
+            if let Some(copies) = self.queued.get_mut(message) {
+                *copies -= 1;
+                if *copies == 0 {
+                    self.queued.remove(message);
+                }
+            }
         }
+        Some((event, ts))
     }
 
     pub fn reset_message_queue(&mut self) {
-        while let Some(_) = self.q.pop() {}
-        self.handled_hash_count = 0;
-        self.handled_messsage_count = 0;
-        self.handled_ask_for = 0;
+        self.q.clear();
+        self.queued.clear();
+        self.next_seq = 0;
+        self.handled_hash_count.clear();
+        self.handled_messsage_count.clear();
+        self.handled_ask_for.clear();
+        self.handled_control_count.clear();
+        self.would_merge_count.clear();
+        self.last_deliver_ts.clear();
     }
 }
 
@@ -73,17 +196,20 @@ impl MessageQueue {
 fn test() {}
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_message_priority() {
     let mut q = MessageQueue::new();
     assert_eq!(
         true,
         q.push(
             Message {
+                message_id: 0,
                 from: 1,
                 to: 1,
                 hop_num: 1,
                 status: MessageStatus::FullMessage,
-                bloomstatus: HashSet::new(),
+                bloomstatus: NodeFilter::default(),
+                chunk: None,
             },
             1,
         )
@@ -92,16 +218,31 @@ fn test_message_priority() {
         false,
         q.push(
             Message {
+                message_id: 0,
                 from: 1,
                 to: 1,
                 hop_num: 1,
                 status: MessageStatus::FullMessage,
-                bloomstatus: HashSet::new(),
+                bloomstatus: NodeFilter::default(),
+                chunk: None,
             },
             2,
         )
     );
-    assert_eq!(q.len(), 1);
+    // both sends are delivered, the identical one counted as a would-be merge
+    assert_eq!(q.len(), 2);
+    assert_eq!(q.would_merge_count(0), 1);
+    assert_eq!(q.handled_messsage_count(0), 2);
+    assert_eq!(q.pop_front().unwrap().1, 1);
+    assert_eq!(q.pop_front().unwrap().1, 2);
+    assert!(q.is_empty());
+
+    // not a merge once the first copy was delivered
+    let message = Message::build_send_hash_message(3, 4, 2);
+    assert!(q.push(message.clone(), 5));
+    q.pop_front();
+    assert!(q.push(message, 6));
+    assert_eq!(q.would_merge_count(0), 1);
 }
 
 #[test]
@@ -109,26 +250,45 @@ fn test_pop_message() {
     let mut q = MessageQueue::new();
     q.push(
         Message {
+            message_id: 0,
             from: 2,
             to: 3,
             hop_num: 2,
             status: MessageStatus::FullMessage,
-            bloomstatus: HashSet::new(),
+            bloomstatus: NodeFilter::default(),
+            chunk: None,
         },
         2,
     );
     q.push(
         Message {
+            message_id: 0,
             from: 1,
             to: 2,
             hop_num: 1,
             status: MessageStatus::FullMessage,
-            bloomstatus: HashSet::new(),
+            bloomstatus: NodeFilter::default(),
+            chunk: None,
         },
         1,
     );
 
-    let (message, ts) = q.pop_front().unwrap();
+    let timer = Timer {
+        node: 3,
+        message_id: 0,
+        tag: 7,
+    };
+    q.push_timer(timer.clone(), 1);
+
+    let (event, ts) = q.pop_front().unwrap();
     assert_eq!(ts, 1);
-    assert_eq!(message.hop_num, 1)
+    assert!(matches!(event, Event::Deliver(message) if message.hop_num == 1));
+    // same ts, pushed later
+    assert_eq!(q.pop_front(), Some((Event::Timer(timer.clone()), 1)));
+    assert_eq!(q.pop_front().unwrap().1, 2);
+    // timers do not count as deliveries
+    q.push_timer(timer, 9);
+    q.pop_front();
+    assert_eq!(q.last_deliver_ts(0), 2);
+    assert_eq!(q.last_deliver_ts(1), 0);
 }
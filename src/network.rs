@@ -0,0 +1,418 @@
+use crate::{
+    bandwidth::Links,
+    behaviour::{Behaviour, Behaviours},
+    failure::Failures,
+    latency::{DelayKind, LatencyModel},
+    loss::PacketLoss,
+    message::{Message, MessageId, NodeId},
+    message_queue::{Event, MessageQueue, TimeStamp, Timer},
+    node_status::NodeStatus,
+    params_packet::{ParamsError, ParamsPacket},
+    partition::Partitions,
+    sim_rng::{new_rng, stream_rng, SimRng, LATENCY_STREAM, TOPOLOGY_STREAM},
+    topology::Topology,
+    traffic::TrafficStats,
+};
+
+/// one broadcast message: its sub seed, source and ts it was sent at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Broadcast {
+    pub message_seed: u64,
+    pub source: NodeId,
+    pub start_ts: TimeStamp,
+}
+
+/// Everything a protocol runs on: the nodes, the links between them, the queue of events
+/// in flight, the rng, and what is measured of each message.
+#[derive(Debug)]
+pub struct Network {
+    /// All necessary params that was set at first time.
+    /// will not change while process.
+    params: ParamsPacket,
+    /// To simulator nodes spread a message. use rand() delay to sync nodes actions in one thread.
+    message_queue: MessageQueue,
+    /// To record nodes' status about each message, by message id.
+    node_status: Vec<Vec<NodeStatus>>,
+    /// messages in flight, by message id.
+    broadcasts: Vec<Broadcast>,
+    /// rng of current simulated message, reseeded by each message's sub seed.
+    rng: SimRng,
+    /// which nodes each node can send to, same for all messages.
+    topology: Box<dyn Topology>,
+    /// delay of every send, same for all messages.
+    latency: Box<dyn LatencyModel>,
+    /// upload/download link of each node, for transmission and queueing delay.
+    links: Links,
+    /// bytes sent for messages in flight.
+    traffic: TrafficStats,
+    /// offline nodes and churn of current message.
+    failures: Failures,
+    /// which sends of current message are lost on the link.
+    packet_loss: PacketLoss,
+    /// which nodes cannot reach each other over time.
+    partitions: Partitions,
+    /// how each node follows the protocol for current message.
+    behaviours: Behaviours,
+}
+
+// getter
+impl Network {
+    pub fn params(&self) -> &ParamsPacket {
+        &self.params
+    }
+    pub fn node_size(&self) -> usize {
+        self.topology.node_size()
+    }
+    pub fn node_status(&self, message_id: MessageId) -> &[NodeStatus] {
+        &self.node_status[message_id]
+    }
+    pub fn node_status_mut(&mut self, message_id: MessageId, node: NodeId) -> &mut NodeStatus {
+        &mut self.node_status[message_id][node]
+    }
+    pub fn broadcast(&self, message_id: MessageId) -> Broadcast {
+        self.broadcasts[message_id]
+    }
+    pub fn message_queue(&self) -> &MessageQueue {
+        &self.message_queue
+    }
+    pub fn rng(&mut self) -> &mut SimRng {
+        &mut self.rng
+    }
+    pub fn topology(&self) -> &dyn Topology {
+        &*self.topology
+    }
+    pub fn latency(&self) -> &dyn LatencyModel {
+        &*self.latency
+    }
+    pub fn traffic(&self) -> &TrafficStats {
+        &self.traffic
+    }
+    pub fn failures(&self) -> &Failures {
+        &self.failures
+    }
+    pub fn packet_loss(&self) -> &PacketLoss {
+        &self.packet_loss
+    }
+    pub fn partitions(&self) -> &Partitions {
+        &self.partitions
+    }
+    pub fn behaviours(&self) -> &Behaviours {
+        &self.behaviours
+    }
+    pub fn behaviours_mut(&mut self) -> &mut Behaviours {
+        &mut self.behaviours
+    }
+}
+
+impl Network {
+    /// `Err` if `params` are not valid or their topology or latency could not be drawn.
+    pub fn new(params: ParamsPacket) -> Result<Network, ParamsError> {
+        params.validate()?;
+        let all_node_size: usize = params.node_size() as usize;
+        let rng = new_rng(params.seed());
+        let topology = params
+            .topology()
+            .build(
+                all_node_size,
+                &mut stream_rng(params.seed(), TOPOLOGY_STREAM),
+            )
+            .map_err(ParamsError::InvalidTopology)?;
+        let latency = params
+            .latency()
+            .build(&params, &mut stream_rng(params.seed(), LATENCY_STREAM))
+            .map_err(ParamsError::InvalidLatency)?;
+        let links = Links::new(
+            all_node_size,
+            params.upload_bandwidth(),
+            params.download_bandwidth(),
+        );
+        let failures = Failures::new(
+            all_node_size,
+            params.offline_fraction(),
+            params.churn_rate(),
+        );
+        let packet_loss = PacketLoss::new(params.packet_loss());
+        let partitions = params.partitions().build(all_node_size);
+        let behaviours = Behaviours::new(all_node_size, params.behaviours());
+        Ok(Network {
+            params,
+            message_queue: MessageQueue::new(),
+            node_status: Vec::new(),
+            broadcasts: Vec::new(),
+            rng,
+            topology,
+            latency,
+            links,
+            traffic: TrafficStats::new(all_node_size),
+            failures,
+            packet_loss,
+            partitions,
+            behaviours,
+        })
+    }
+
+    /// clear everything for `message_count` messages spreading together: the protocol uses
+    /// `rng`, failures, loss and behaviours are drawn from `seed`, and `source` is sure to be
+    /// live and honest.
+    pub fn reset(&mut self, seed: u64, rng: SimRng, message_count: usize, source: NodeId) {
+        self.rng = rng;
+        self.message_queue.reset_message_queue();
+        self.links.reset();
+        self.traffic.reset();
+        self.broadcasts.clear();
+        let node_size = self.node_size();
+        let filter = self.params.bloom_filter().new_filter();
+        self.node_status.resize_with(message_count, || {
+            vec![NodeStatus::new(filter.clone()); node_size]
+        });
+        self.node_status
+            .iter_mut()
+            .flatten()
+            .for_each(|ns| ns.reset_status());
+        self.failures.reset(seed, source);
+        self.packet_loss.reset(seed);
+        self.partitions.reset();
+        self.behaviours.reset(seed, source);
+    }
+
+    /// create a src broadcast message with the next message id, sent at `start_ts`.
+    /// It arrives at `source` itself, which then spreads it.
+    pub fn send_broadcast(&mut self, message_seed: u64, source: NodeId, start_ts: TimeStamp) {
+        let mut message = Message::build_send_full_message(source, source, 0);
+        message.message_id = self.broadcasts.len();
+        message.bloomstatus = self.params.bloom_filter().new_filter();
+        self.message_queue.push(message, start_ts);
+        self.broadcasts.push(Broadcast {
+            message_seed,
+            source,
+            start_ts,
+        });
+    }
+
+    /// next event, skipping messages that arrive at an offline node. A timer of an offline
+    /// node fires once it is back, as its protocol state outlives the crash.
+    pub fn next_event(&mut self) -> Option<(Event, TimeStamp)> {
+        while let Some((event, ts)) = self.message_queue.pop_front() {
+            match &event {
+                Event::Deliver(message) if !self.failures.is_online(message.to, ts) => {
+                    log::debug!("{} is offline, drop message {:?}", message.to, message);
+                    self.failures.record_offline_drop(message.message_id);
+                    continue;
+                }
+                Event::Timer(timer) if !self.failures.is_online(timer.node, ts) => {
+                    let rejoin_ts = self.failures.rejoin_ts(timer.node);
+                    log::debug!(
+                        "{} is offline until {:?}, hold {:?}",
+                        timer.node,
+                        rejoin_ts,
+                        timer
+                    );
+                    if let Some(rejoin_ts) = rejoin_ts {
+                        self.message_queue.push_timer(timer.clone(), rejoin_ts);
+                    }
+                    continue;
+                }
+                _ => return Some((event, ts)),
+            }
+        }
+        None
+    }
+
+    /// bring every node to its state at `ts`, once the messages stopped spreading.
+    pub fn advance_to(&mut self, ts: TimeStamp) {
+        self.failures.advance_to(ts);
+    }
+
+    /// push `message` into queue. It arrives after the latency of `kind` plus the time its bytes
+    /// take on the sender's upload and the receiver's download link.
+    /// A message lost on the link only takes the sender's upload time and is never pushed.
+    /// One between partitioned nodes is not sent at all.
+    pub fn send(&mut self, message: Message, ts: TimeStamp, kind: DelayKind) -> (TimeStamp, bool) {
+        if !self.partitions.can_reach(message.from, message.to, ts) {
+            log::debug!("  partitioned {:?}", message);
+            self.partitions.record_drop(message.message_id);
+            return (ts, false);
+        }
+        let propagation = self
+            .latency
+            .delay(message.from, message.to, kind, &mut self.rng);
+        if self.behaviours.of(message.from) == Behaviour::Spammer {
+            self.behaviours.record_spam(message.message_id);
+        }
+        let bytes = message.wire_bytes(self.params.payload_size());
+        self.traffic.record_send(&message, bytes);
+        if self.packet_loss.is_lost(message.from, message.to) {
+            let (_, send_done) = self.links.upload(message.from, ts, bytes.total() as u32);
+            log::debug!("  lost {:?}", message);
+            self.packet_loss.record_lost(&message);
+            return (send_done, false);
+        }
+        self.traffic.record_recv(&message, bytes);
+        let next_ts = self.links.transmit(
+            message.from,
+            message.to,
+            ts,
+            bytes.total() as u32,
+            propagation,
+        );
+        let res = self.message_queue.push(message, next_ts);
+        (next_ts, res)
+    }
+
+    /// fire `timer` at `ts`.
+    pub fn set_timer(&mut self, timer: Timer, ts: TimeStamp) {
+        self.message_queue.push_timer(timer, ts);
+    }
+
+    /// `max_num` distinct random neighbours of `node`.
+    pub fn random_neighbours(&mut self, node: NodeId, max_num: usize) -> Vec<NodeId> {
+        self.topology
+            .random_neighbours(node, max_num, &mut self.rng)
+    }
+}
+
+#[cfg(test)]
+impl Network {
+    /// a network of `params` reset for one message from `source`, for unit tests of what a
+    /// protocol does on it.
+    pub(crate) fn for_message(params: ParamsPacket, source: NodeId) -> Network {
+        let seed = params.seed();
+        let mut net = Network::new(params).unwrap();
+        net.reset(seed, new_rng(seed), 1, source);
+        net
+    }
+
+    /// pop every queued event in ts order, whether its node is online or not.
+    pub(crate) fn drain_events(&mut self) -> Vec<(Event, TimeStamp)> {
+        std::iter::from_fn(|| self.message_queue.pop_front()).collect()
+    }
+
+    /// pop every queued event, keeping the messages sent.
+    pub(crate) fn drain_messages(&mut self) -> Vec<Message> {
+        self.drain_events()
+            .into_iter()
+            .filter_map(|(event, _)| match event {
+                Event::Deliver(message) => Some(message),
+                Event::Timer(_) => None,
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_new_network() {
+    assert!(Network::new(ParamsPacket::new(100, 3, 4, 1)).is_ok());
+    // `new` skips the checks of the builder
+    assert_eq!(
+        Network::new(ParamsPacket::new(0, 3, 4, 1)).unwrap_err(),
+        ParamsError::NodeSizeTooSmall(0)
+    );
+}
+
+#[test]
+fn test_send() {
+    use crate::{loss::LossKind, params_packet::ParamsPacketBuilder};
+
+    let base = ParamsPacket::builder(100, 4, 4, 1).seed(1);
+    let network = |b: ParamsPacketBuilder| Network::for_message(b.build().unwrap(), 0);
+    let message = |from, to| Message::build_send_full_message(from, to, 1);
+
+    // messages between the groups are not sent while a split is in effect
+    let mut net = network(base.clone().partitions("split:2:100:500".parse().unwrap()));
+    net.send(message(0, 60), 0, DelayKind::Message);
+    assert_eq!(
+        net.send(message(0, 60), 100, DelayKind::Message),
+        (100, false)
+    );
+    net.send(message(0, 10), 100, DelayKind::Message);
+    net.send(message(0, 60), 500, DelayKind::Message);
+    assert_eq!(net.partitions().drop_count(0), 1);
+    let delivered: Vec<NodeId> = net.drain_messages().iter().map(|m| m.to).collect();
+    assert_eq!(delivered, vec![60, 10, 60]);
+
+    // a lost message is counted and takes up the upload link, but is never delivered
+    let mut net = network(base.clone().packet_loss(LossKind::Bernoulli { p: 1.0 }));
+    assert!(!net.send(message(0, 1), 0, DelayKind::Message).1);
+    assert_eq!(net.packet_loss.lost_message_count(0), 1);
+    assert!(net.drain_events().is_empty());
+
+    // bytes take time on the links
+    let base = base.payload_size(8000).download_bandwidth(0);
+    let unlimited = network(base.clone().upload_bandwidth(0))
+        .send(message(0, 1), 0, DelayKind::Message)
+        .0;
+    let limited = network(base.upload_bandwidth(64))
+        .send(message(0, 1), 0, DelayKind::Message)
+        .0;
+    assert!(limited >= unlimited + 1000);
+}
+
+#[test]
+fn test_next_event_drops_offline() {
+    use crate::params_packet::ParamsPacketBuilder;
+
+    let params = ParamsPacket::builder(100, 4, 4, 1)
+        .seed(1)
+        .offline_fraction(0.5)
+        .build()
+        .unwrap();
+    let mut net = Network::for_message(params, 0);
+    let online: Vec<bool> = net.failures.online().to_vec();
+    assert!(online[0]);
+    for node in 0..100 {
+        net.message_queue.push(
+            Message::build_send_full_message(0, node, 1),
+            node as TimeStamp,
+        );
+    }
+    let mut delivered = Vec::new();
+    while let Some((Event::Deliver(message), _)) = net.next_event() {
+        delivered.push(message.to);
+    }
+    let live: Vec<NodeId> = (0..100).filter(|node| online[*node]).collect();
+    assert_eq!(delivered, live);
+    assert_eq!(net.failures.offline_drop_count(0), 100 - live.len() as u32);
+}
+
+#[test]
+fn test_next_event_holds_offline_timers() {
+    use crate::{params_packet::ParamsPacket, plumtree::Plumtree, protocol::Protocol};
+
+    let params = ParamsPacket::builder(100, 4, 4, 1)
+        .seed(1)
+        .churn_rate(1.0)
+        .build()
+        .unwrap();
+    let mut net = Network::for_message(params, 0);
+    let mut plumtree = Plumtree::new(200);
+    plumtree.start(&net);
+    // a node that crashes while waiting to graft
+    let (node, crash_ts) = (1..100)
+        .find_map(|node| {
+            (200..5000)
+                .step_by(10)
+                .find(|ts| !net.failures.is_online(node, *ts))
+                .map(|ts| (node, ts))
+        })
+        .unwrap();
+    let rejoin_ts = net.failures.rejoin_ts(node).unwrap();
+    assert!(rejoin_ts > crash_ts);
+    let announce = Message::build_send_hash_message(0, node, 1);
+    plumtree.on_receive(&mut net, announce, crash_ts - 200);
+
+    let mut graft_ts = None;
+    while let Some((event, ts)) = net.next_event() {
+        match event {
+            Event::Deliver(message) => plumtree.on_receive(&mut net, message, ts),
+            Event::Timer(timer) => {
+                assert_eq!(timer.node, node);
+                assert_eq!(ts, rejoin_ts);
+                plumtree.on_timer(&mut net, timer, ts);
+                graft_ts = Some(ts);
+            }
+        }
+    }
+    // the graft is sent once it is back, not lost with the crash
+    assert_eq!(graft_ts, Some(rejoin_ts));
+    assert_eq!(net.message_queue.handled_control_count(0), 1);
+}
@@ -1,6 +1,6 @@
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
-use crate::message::NodeId;
+use crate::{behaviour::SPAM_BUDGET, bloom::NodeFilter, message::NodeId, message_queue::TimeStamp};
 
 /// one node status about one message. Like `IF` and `HOW MANY TIMES` has recvd this message/hash.
 #[derive(Clone, Debug)]
@@ -9,8 +9,19 @@ pub struct NodeStatus {
     recv_hash_count: u32,
     recv_message_count: u32,
     handle_hash_count: u32,
-    bloom_filter_info: Vec<bool>, // from each nodes aspect, if other nodes has recvd message
+    bloom_filter: NodeFilter, // from each nodes aspect, if other nodes has recvd message
+    /// dst skipped because `bloom_filter` says it has the message.
+    bloom_skip_count: u32,
+    /// of which the filter was wrong, the dst was never inserted.
+    false_positive_skip_count: u32,
     send_ask_for_ts: u32,
+    /// when this node first recvd full message.
+    recv_full_message_ts: Option<TimeStamp>,
+    /// distinct coded chunks recvd, and when the first one came.
+    recv_chunks: BTreeSet<u32>,
+    first_chunk_ts: Option<TimeStamp>,
+    /// copies forwarded as a spammer.
+    spam_forward_count: u32,
 }
 
 // getter
@@ -27,57 +38,67 @@ impl NodeStatus {
     pub fn handle_hash_count(&self) -> u32 {
         self.handle_hash_count
     }
-    pub fn get_already_recvd_nodes(&self) -> HashSet<NodeId> {
-        let mut set = HashSet::<NodeId>::new();
-        self.bloom_filter_info
-            .iter()
-            .enumerate()
-            .filter(|(_, &value)| value)
-            .for_each(|(index, _)| {
-                set.insert(index);
-            });
-        set
+    pub fn bloom_filter(&self) -> &NodeFilter {
+        &self.bloom_filter
+    }
+    pub fn bloom_skip_count(&self) -> u32 {
+        self.bloom_skip_count
+    }
+    pub fn false_positive_skip_count(&self) -> u32 {
+        self.false_positive_skip_count
     }
     pub fn send_ask_for_ts(&self) -> u32 {
         self.send_ask_for_ts
     }
+    pub fn recv_full_message_ts(&self) -> Option<TimeStamp> {
+        self.recv_full_message_ts
+    }
+    pub fn recv_chunk_count(&self) -> u32 {
+        self.recv_chunks.len() as u32
+    }
+    pub fn first_chunk_ts(&self) -> Option<TimeStamp> {
+        self.first_chunk_ts
+    }
 }
 
 pub trait UpdateBloomFilter<InputType> {
     fn update_bloom_filter(&mut self, recv_nodes: &InputType);
 }
 
-impl UpdateBloomFilter<HashSet<NodeId>> for NodeStatus {
-    fn update_bloom_filter(&mut self, recv_nodes: &HashSet<NodeId>) {
-        recv_nodes.iter().for_each(|n| {
-            self.bloom_filter_info[*n] |= true;
-        })
+impl UpdateBloomFilter<NodeFilter> for NodeStatus {
+    fn update_bloom_filter(&mut self, recv_nodes: &NodeFilter) {
+        self.bloom_filter.merge(recv_nodes)
     }
 }
 
 impl UpdateBloomFilter<Vec<NodeId>> for NodeStatus {
     fn update_bloom_filter(&mut self, recv_nodes: &Vec<NodeId>) {
-        recv_nodes
-            .iter()
-            .for_each(|n| self.bloom_filter_info[*n] |= true)
+        recv_nodes.iter().for_each(|n| self.bloom_filter.insert(*n))
     }
 }
 
 impl UpdateBloomFilter<NodeId> for NodeStatus {
     fn update_bloom_filter(&mut self, recv_nodes: &NodeId) {
-        self.bloom_filter_info[*recv_nodes] |= true;
+        self.bloom_filter.insert(*recv_nodes);
     }
 }
 
 impl NodeStatus {
-    pub fn new(node_size: usize) -> NodeStatus {
+    /// `bloom_filter` is an empty filter of the kind in use.
+    pub fn new(bloom_filter: NodeFilter) -> NodeStatus {
         NodeStatus {
             has_recv_full_message: false,
             recv_hash_count: 0,
             recv_message_count: 0,
             handle_hash_count: 0,
-            bloom_filter_info: vec![false; node_size],
+            bloom_filter,
+            bloom_skip_count: 0,
+            false_positive_skip_count: 0,
             send_ask_for_ts: 0,
+            recv_full_message_ts: None,
+            recv_chunks: BTreeSet::new(),
+            first_chunk_ts: None,
+            spam_forward_count: 0,
         }
     }
 
@@ -86,15 +107,40 @@ impl NodeStatus {
         self.recv_hash_count + self.recv_message_count > max_handle_count
     }
 
-    pub fn record_recv_message(&mut self) {
+    pub fn record_recv_message(&mut self, ts: TimeStamp) {
+        if !self.has_recv_full_message {
+            self.recv_full_message_ts = Some(ts);
+        }
         self.has_recv_full_message |= true;
         self.recv_message_count += 1;
     }
 
+    /// `true` if chunk `index` is new to this node.
+    pub fn record_recv_chunk(&mut self, index: u32, ts: TimeStamp) -> bool {
+        self.first_chunk_ts.get_or_insert(ts);
+        self.recv_chunks.insert(index)
+    }
+
+    /// a spammer forwards one more copy, `false` once it has used up its `SPAM_BUDGET`.
+    pub fn spend_spam_budget(&mut self) -> bool {
+        if self.spam_forward_count >= SPAM_BUDGET {
+            return false;
+        }
+        self.spam_forward_count += 1;
+        true
+    }
+
     pub fn record_recv_hash(&mut self) {
         self.recv_hash_count += 1;
     }
 
+    pub fn record_bloom_skip(&mut self, false_positive: bool) {
+        self.bloom_skip_count += 1;
+        if false_positive {
+            self.false_positive_skip_count += 1;
+        }
+    }
+
     pub fn record_send_ask_for(&mut self, ts: u32) {
         self.send_ask_for_ts = ts;
     }
@@ -104,9 +150,13 @@ impl NodeStatus {
         self.recv_hash_count = 0;
         self.recv_message_count = 0;
         self.handle_hash_count = 0;
-        for _recvd in &mut self.bloom_filter_info {
-            *_recvd = false;
-        }
+        self.bloom_filter.clear();
+        self.bloom_skip_count = 0;
+        self.false_positive_skip_count = 0;
         self.send_ask_for_ts = 0;
+        self.recv_full_message_ts = None;
+        self.recv_chunks.clear();
+        self.first_chunk_ts = None;
+        self.spam_forward_count = 0;
     }
 }
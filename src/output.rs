@@ -0,0 +1,348 @@
+use std::{
+    fmt, fs,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::performance_result::ResultPack;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// one markdown table row per parameter set, as in `datas/`.
+    Markdown,
+    /// comma separated values with a header line.
+    Csv,
+    /// one json object per line.
+    JsonLines,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" | "json-lines" => Ok(OutputFormat::JsonLines),
+            _ => Err(format!(
+                "unknown output format `{}`, expect one of: markdown, csv, jsonl",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Markdown => write!(f, "markdown"),
+            OutputFormat::Csv => write!(f, "csv"),
+            OutputFormat::JsonLines => write!(f, "jsonl"),
+        }
+    }
+}
+
+/// Write `ResultPack`s to stdout or a file in the chosen format, header only once.
+pub struct ResultWriter {
+    out: Box<dyn Write>,
+    format: OutputFormat,
+    header_written: bool,
+}
+
+impl ResultWriter {
+    pub fn new(out: Box<dyn Write>, format: OutputFormat) -> ResultWriter {
+        ResultWriter {
+            out,
+            format,
+            header_written: false,
+        }
+    }
+
+    pub fn stdout(format: OutputFormat) -> ResultWriter {
+        ResultWriter::new(Box::new(io::stdout()), format)
+    }
+
+    /// `-` means stdout.
+    pub fn create<P: AsRef<Path>>(path: P, format: OutputFormat) -> io::Result<ResultWriter> {
+        if path.as_ref() == Path::new("-") {
+            return Ok(ResultWriter::stdout(format));
+        }
+        let file = fs::File::create(path)?;
+        Ok(ResultWriter::new(Box::new(BufWriter::new(file)), format))
+    }
+
+    /// write one record of the parameter set.
+    pub fn write(&mut self, rp: &ResultPack) -> io::Result<()> {
+        match self.format {
+            OutputFormat::Markdown => {
+                if !self.header_written {
+                    let header = ResultPack::markdown_header();
+                    writeln!(self.out, "{}", header)?;
+                    writeln!(self.out, "{}", markdown_separator(header))?;
+                    self.header_written = true;
+                }
+                writeln!(self.out, "{}", rp.markdown_row())
+            }
+            OutputFormat::Csv | OutputFormat::JsonLines => self.write_record(&rp.summary_record()),
+        }
+    }
+
+    /// write one record of each simulated message of the parameter set.
+    pub fn write_messages(&mut self, rp: &ResultPack) -> io::Result<()> {
+        for record in rp.message_records() {
+            self.write_record(&record)?;
+        }
+        Ok(())
+    }
+
+    /// write one record of each source of the parameter set.
+    pub fn write_sources(&mut self, rp: &ResultPack) -> io::Result<()> {
+        for record in rp.source_records() {
+            self.write_record(&record)?;
+        }
+        Ok(())
+    }
+
+    /// write one record of each group of each partition of the parameter set.
+    pub fn write_partitions(&mut self, rp: &ResultPack) -> io::Result<()> {
+        for record in rp.partition_records() {
+            self.write_record(&record)?;
+        }
+        Ok(())
+    }
+
+    /// write any flat serializable struct, header (if any) taken from the first record.
+    pub fn write_record<T: Serialize>(&mut self, record: &T) -> io::Result<()> {
+        let value = serde_json::to_value(record)?;
+        if self.format == OutputFormat::JsonLines {
+            return writeln!(self.out, "{}", value);
+        }
+        let fields = match value.as_object() {
+            Some(fields) => fields,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "only struct records can be written as table rows",
+                ))
+            }
+        };
+        let keys: Vec<&str> = fields.keys().map(|k| k.as_str()).collect();
+        let values: Vec<String> = fields.values().map(field_to_string).collect();
+        match self.format {
+            OutputFormat::Csv => {
+                if !self.header_written {
+                    writeln!(self.out, "{}", keys.join(","))?;
+                    self.header_written = true;
+                }
+                let values: Vec<String> = values.iter().map(|v| csv_escape(v)).collect();
+                writeln!(self.out, "{}", values.join(","))
+            }
+            _ => {
+                if !self.header_written {
+                    let header = format!("|{}|", keys.join("|"));
+                    writeln!(self.out, "{}", header)?;
+                    writeln!(self.out, "{}", markdown_separator(&header))?;
+                    self.header_written = true;
+                }
+                writeln!(self.out, "|{} |", values.join(" | "))
+            }
+        }
+    }
+
+    /// write every result, then how each one differs from the first (the baseline).
+    /// csv/json records carry the relative change as extra `*_vs_baseline` fields instead.
+    pub fn write_comparison(&mut self, rps: &[ResultPack]) -> io::Result<()> {
+        if self.format != OutputFormat::Markdown {
+            if let Some(baseline) = rps.first() {
+                for rp in rps {
+                    self.write_record(&ComparisonRecord::new(baseline, rp))?;
+                }
+            }
+            return Ok(());
+        }
+        for rp in rps {
+            self.write(rp)?;
+        }
+        let baseline = match rps.first() {
+            Some(baseline) => baseline,
+            None => return Ok(()),
+        };
+        match self.format {
+            OutputFormat::Csv | OutputFormat::JsonLines => Ok(()),
+            OutputFormat::Markdown => {
+                let header = "|N|t|k|recv node size vs baseline|send message count vs baseline|send hash count vs baseline|send ask for count vs baseline|";
+                writeln!(self.out)?;
+                writeln!(self.out, "{}", header)?;
+                writeln!(self.out, "{}", markdown_separator(header))?;
+                for rp in rps {
+                    writeln!(
+                        self.out,
+                        "|{} | {} | {} | {} | {} | {} | {} |",
+                        rp.params().node_size(),
+                        rp.params().t(),
+                        rp.params().k(),
+                        relative_change(baseline.avg_recv_node_size(), rp.avg_recv_node_size()),
+                        relative_change(
+                            baseline.avg_send_message_count(),
+                            rp.avg_send_message_count()
+                        ),
+                        relative_change(baseline.avg_send_hash_count(), rp.avg_send_hash_count()),
+                        relative_change(
+                            baseline.avg_send_ask_for_count(),
+                            rp.avg_send_ask_for_count()
+                        ),
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[derive(Serialize)]
+struct ComparisonRecord {
+    #[serde(flatten)]
+    summary: crate::performance_result::SummaryRecord,
+    recv_node_size_vs_baseline: Option<f64>,
+    send_message_count_vs_baseline: Option<f64>,
+    send_hash_count_vs_baseline: Option<f64>,
+    send_ask_for_count_vs_baseline: Option<f64>,
+}
+
+impl ComparisonRecord {
+    fn new(baseline: &ResultPack, rp: &ResultPack) -> ComparisonRecord {
+        let ratio = |b: f64, v: f64| {
+            if b == 0.0 {
+                None
+            } else {
+                Some((v - b) / b)
+            }
+        };
+        ComparisonRecord {
+            summary: rp.summary_record(),
+            recv_node_size_vs_baseline: ratio(
+                baseline.avg_recv_node_size(),
+                rp.avg_recv_node_size(),
+            ),
+            send_message_count_vs_baseline: ratio(
+                baseline.avg_send_message_count(),
+                rp.avg_send_message_count(),
+            ),
+            send_hash_count_vs_baseline: ratio(
+                baseline.avg_send_hash_count(),
+                rp.avg_send_hash_count(),
+            ),
+            send_ask_for_count_vs_baseline: ratio(
+                baseline.avg_send_ask_for_count(),
+                rp.avg_send_ask_for_count(),
+            ),
+        }
+    }
+}
+
+fn field_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn markdown_separator(header: &str) -> String {
+    let columns = header.matches('|').count() - 1;
+    format!("|{}", "-|".repeat(columns))
+}
+
+fn relative_change(baseline: f64, value: f64) -> String {
+    if baseline == 0.0 {
+        return if value == 0.0 {
+            "+0.00%".to_string()
+        } else {
+            "n/a".to_string()
+        };
+    }
+    format!("{:+.2}%", (value - baseline) / baseline * 100.0)
+}
+
+#[test]
+fn test_markdown_separator() {
+    assert_eq!(markdown_separator("|N|t|k|"), "|-|-|-|");
+}
+
+#[test]
+fn test_csv_escape() {
+    assert_eq!(csv_escape("100"), "100");
+    assert_eq!(csv_escape("a,b"), "\"a,b\"");
+    assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+}
+
+#[test]
+fn test_write_records() {
+    use crate::{params_packet::ParamsPacket, rrs_simulator::RRSSimulator};
+
+    let params = ParamsPacket::builder(50, 3, 3, 4).seed(1).build().unwrap();
+    let rp = RRSSimulator::new(params).unwrap().run();
+
+    let buf = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut writer = ResultWriter::new(Box::new(SharedBuf(buf.clone())), OutputFormat::Csv);
+    writer.write(&rp).unwrap();
+    writer.write(&rp).unwrap();
+    let csv = String::from_utf8(buf.borrow().clone()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("node_size,t,k,n,seed,"));
+    assert_eq!(lines[1], lines[2]);
+    assert_eq!(lines[0].split(',').count(), lines[1].split(',').count());
+
+    let buf = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+    let mut writer = ResultWriter::new(Box::new(SharedBuf(buf.clone())), OutputFormat::JsonLines);
+    writer.write_messages(&rp).unwrap();
+    let jsonl = String::from_utf8(buf.borrow().clone()).unwrap();
+    assert_eq!(jsonl.lines().count(), 4);
+    for (index, line) in jsonl.lines().enumerate() {
+        let record: Value = serde_json::from_str(line).unwrap();
+        assert_eq!(record["message_index"], index);
+        assert_eq!(
+            record["message_seed"],
+            rp.each_result_data()[index].message_seed()
+        );
+        assert_eq!(record["node_size"], 50);
+    }
+}
+
+#[cfg(test)]
+struct SharedBuf(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_relative_change() {
+    assert_eq!(relative_change(100.0, 110.0), "+10.00%");
+    assert_eq!(relative_change(100.0, 95.0), "-5.00%");
+    assert_eq!(relative_change(0.0, 0.0), "+0.00%");
+    assert_eq!(relative_change(0.0, 1.0), "n/a");
+}
@@ -0,0 +1,598 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::{
+    behaviour::BehaviourMix, bloom::BloomKind, latency::LatencyKind, loss::LossKind,
+    partition::PartitionKind, protocol::ProtocolKind, source::SourceKind, topology::TopologyKind,
+};
+
+pub const DEFAULT_MAX_HOP_NUM: u32 = 10;
+pub const DEFAULT_EACH_HANDLE_COUNT: u32 = 3;
+pub const DEFAULT_SEND_ASK_INTERVAL: u32 = 50;
+pub const DEFAULT_SEED: u64 = 0;
+pub const DEFAULT_PAYLOAD_SIZE: u32 = 1024;
+/// unlimited
+pub const DEFAULT_BANDWIDTH: u32 = 0;
+/// toggles per node per second, one per ms: ts are whole ms, faster churn is not seen.
+pub const MAX_CHURN_RATE: f64 = 1000.0;
+// delay:
+pub const DEFAULT_SEND_MESSAGE_DELAY_RANGE: (u32, u32) = (100, 120);
+pub const DEFAULT_SEND_HASH_DELAY_RANGE: (u32, u32) = (100, 140);
+pub const DEFAULT_SEND_ASK_FOR_DELAY_RANGE: (u32, u32) = (100, 130);
+pub const DEFAULT_SEND_REPLY_ASK_DELAY_RANGE: (u32, u32) = (100, 130);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParamsPacket {
+    /// network node scale
+    node_size: u32,
+    /// each round , node will select `t` neighbours and spread message
+    t: u32,
+    /// after message has been passed by `k` round, it will be transform into msg_hash header message and goes on.
+    k: u32,
+    /// simulate message count
+    n: u32,
+    /// message/hash will not be spread any more after `max_hop_num` hops.
+    max_hop_num: u32,
+    /// node stop spreading once it has recvd message/hash more than `each_handle_count` times.
+    each_handle_count: u32,
+    /// min interval between two ask for message sent by the same node.
+    send_ask_interval: u32,
+    /// delay range `[lo, hi)` of sending full message.
+    send_message_delay_range: (u32, u32),
+    /// delay range `[lo, hi)` of sending msg_hash header message.
+    send_hash_delay_range: (u32, u32),
+    /// delay range `[lo, hi)` of sending ask for message.
+    send_ask_for_delay_range: (u32, u32),
+    /// delay range `[lo, hi)` of replying full message to ask for message.
+    send_reply_ask_delay_range: (u32, u32),
+    /// seed of the simulator rng. Each message's sub seed is derived from it.
+    seed: u64,
+    /// overlay network nodes select neighbours from.
+    topology: TopologyKind,
+    /// how long a message takes between two nodes. `uniform` uses the delay ranges above.
+    latency: LatencyKind,
+    /// bytes of the broadcast content carried by full messages.
+    payload_size: u32,
+    /// upload bandwidth of every node in kbit/s, `0` means unlimited.
+    upload_bandwidth: u32,
+    /// download bandwidth of every node in kbit/s, `0` means unlimited.
+    download_bandwidth: u32,
+    /// how `bloomstatus` and each node's known set are kept, exact or a real bloom filter.
+    bloom_filter: BloomKind,
+    /// fraction of nodes (apart from the source) offline from the start of each message.
+    offline_fraction: f64,
+    /// crash/join toggles per node per second while a message spreads.
+    churn_rate: f64,
+    /// how sends are lost on the link.
+    packet_loss: LossKind,
+    /// which nodes cannot reach each other, and when.
+    partitions: PartitionKind,
+    /// fraction of nodes misbehaving in each way.
+    behaviours: BehaviourMix,
+    /// which node broadcasts each message.
+    source: SourceKind,
+    /// messages per second sent concurrently on one network, `0` means one after another.
+    arrival_rate: f64,
+    /// how nodes spread each message.
+    protocol: ProtocolKind,
+}
+
+// getter
+impl ParamsPacket {
+    pub fn node_size(&self) -> u32 {
+        self.node_size
+    }
+    pub fn t(&self) -> u32 {
+        self.t
+    }
+    pub fn k(&self) -> u32 {
+        self.k
+    }
+    pub fn n(&self) -> u32 {
+        self.n
+    }
+    pub fn max_hop_num(&self) -> u32 {
+        self.max_hop_num
+    }
+    pub fn each_handle_count(&self) -> u32 {
+        self.each_handle_count
+    }
+    pub fn send_ask_interval(&self) -> u32 {
+        self.send_ask_interval
+    }
+    pub fn send_message_delay_range(&self) -> (u32, u32) {
+        self.send_message_delay_range
+    }
+    pub fn send_hash_delay_range(&self) -> (u32, u32) {
+        self.send_hash_delay_range
+    }
+    pub fn send_ask_for_delay_range(&self) -> (u32, u32) {
+        self.send_ask_for_delay_range
+    }
+    pub fn send_reply_ask_delay_range(&self) -> (u32, u32) {
+        self.send_reply_ask_delay_range
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    pub fn topology(&self) -> TopologyKind {
+        self.topology
+    }
+    pub fn latency(&self) -> &LatencyKind {
+        &self.latency
+    }
+    pub fn payload_size(&self) -> u32 {
+        self.payload_size
+    }
+    pub fn upload_bandwidth(&self) -> u32 {
+        self.upload_bandwidth
+    }
+    pub fn download_bandwidth(&self) -> u32 {
+        self.download_bandwidth
+    }
+    pub fn bloom_filter(&self) -> BloomKind {
+        self.bloom_filter
+    }
+    pub fn offline_fraction(&self) -> f64 {
+        self.offline_fraction
+    }
+    pub fn churn_rate(&self) -> f64 {
+        self.churn_rate
+    }
+    pub fn packet_loss(&self) -> LossKind {
+        self.packet_loss
+    }
+    pub fn partitions(&self) -> &PartitionKind {
+        &self.partitions
+    }
+    pub fn behaviours(&self) -> BehaviourMix {
+        self.behaviours
+    }
+    pub fn source(&self) -> &SourceKind {
+        &self.source
+    }
+    pub fn arrival_rate(&self) -> f64 {
+        self.arrival_rate
+    }
+    pub fn protocol(&self) -> ProtocolKind {
+        self.protocol
+    }
+}
+
+impl ParamsPacket {
+    /// params with default protocol constants. Use [`ParamsPacket::builder`] to set others.
+    pub fn new(node_size: u32, t: u32, k: u32, n: u32) -> Self {
+        ParamsPacket {
+            node_size,
+            t,
+            k,
+            n,
+            max_hop_num: DEFAULT_MAX_HOP_NUM,
+            each_handle_count: DEFAULT_EACH_HANDLE_COUNT,
+            send_ask_interval: DEFAULT_SEND_ASK_INTERVAL,
+            send_message_delay_range: DEFAULT_SEND_MESSAGE_DELAY_RANGE,
+            send_hash_delay_range: DEFAULT_SEND_HASH_DELAY_RANGE,
+            send_ask_for_delay_range: DEFAULT_SEND_ASK_FOR_DELAY_RANGE,
+            send_reply_ask_delay_range: DEFAULT_SEND_REPLY_ASK_DELAY_RANGE,
+            seed: DEFAULT_SEED,
+            topology: TopologyKind::Complete,
+            latency: LatencyKind::Uniform,
+            payload_size: DEFAULT_PAYLOAD_SIZE,
+            upload_bandwidth: DEFAULT_BANDWIDTH,
+            download_bandwidth: DEFAULT_BANDWIDTH,
+            bloom_filter: BloomKind::Exact,
+            offline_fraction: 0.0,
+            churn_rate: 0.0,
+            packet_loss: LossKind::None,
+            partitions: PartitionKind::None,
+            behaviours: BehaviourMix::default(),
+            source: SourceKind::default(),
+            arrival_rate: 0.0,
+            protocol: ProtocolKind::default(),
+        }
+    }
+
+    pub fn builder(node_size: u32, t: u32, k: u32, n: u32) -> ParamsPacketBuilder {
+        ParamsPacketBuilder {
+            params: ParamsPacket::new(node_size, t, k, n),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ParamsError> {
+        if self.node_size < 2 {
+            return Err(ParamsError::NodeSizeTooSmall(self.node_size));
+        }
+        if self.t == 0 || self.t >= self.node_size {
+            return Err(ParamsError::InvalidT {
+                t: self.t,
+                node_size: self.node_size,
+            });
+        }
+        if self.n == 0 {
+            return Err(ParamsError::ZeroMessageCount);
+        }
+        if self.max_hop_num == 0 {
+            return Err(ParamsError::ZeroMaxHopNum);
+        }
+        if self.k > self.max_hop_num {
+            return Err(ParamsError::KExceedsMaxHopNum {
+                k: self.k,
+                max_hop_num: self.max_hop_num,
+            });
+        }
+        for (name, range) in [
+            ("send_message_delay_range", self.send_message_delay_range),
+            ("send_hash_delay_range", self.send_hash_delay_range),
+            ("send_ask_for_delay_range", self.send_ask_for_delay_range),
+            (
+                "send_reply_ask_delay_range",
+                self.send_reply_ask_delay_range,
+            ),
+        ] {
+            if range.0 >= range.1 {
+                return Err(ParamsError::EmptyDelayRange { name, range });
+            }
+        }
+        self.topology
+            .validate(self.node_size)
+            .map_err(ParamsError::InvalidTopology)?;
+        self.latency
+            .validate(self.node_size)
+            .map_err(ParamsError::InvalidLatency)?;
+        self.bloom_filter
+            .validate()
+            .map_err(ParamsError::InvalidBloomFilter)?;
+        if !(0.0..1.0).contains(&self.offline_fraction) {
+            return Err(ParamsError::InvalidFailureModel(format!(
+                "offline_fraction must be in [0, 1), got {}",
+                self.offline_fraction
+            )));
+        }
+        if !(0.0..=MAX_CHURN_RATE).contains(&self.churn_rate) {
+            return Err(ParamsError::InvalidFailureModel(format!(
+                "churn_rate must be in [0, {}], got {}",
+                MAX_CHURN_RATE, self.churn_rate
+            )));
+        }
+        self.packet_loss
+            .validate()
+            .map_err(ParamsError::InvalidPacketLoss)?;
+        self.partitions
+            .validate(self.node_size)
+            .map_err(ParamsError::InvalidPartitions)?;
+        self.behaviours
+            .validate()
+            .map_err(ParamsError::InvalidBehaviours)?;
+        self.source
+            .validate(self.node_size, &self.latency)
+            .map_err(ParamsError::InvalidSource)?;
+        self.protocol
+            .validate()
+            .map_err(ParamsError::InvalidProtocol)?;
+        if !self.arrival_rate.is_finite() || self.arrival_rate < 0.0 {
+            return Err(ParamsError::InvalidArrivalRate(format!(
+                "arrival_rate must be finite and not negative, got {}",
+                self.arrival_rate
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct ParamsPacketBuilder {
+    params: ParamsPacket,
+}
+
+impl ParamsPacketBuilder {
+    pub fn max_hop_num(mut self, max_hop_num: u32) -> Self {
+        self.params.max_hop_num = max_hop_num;
+        self
+    }
+    pub fn each_handle_count(mut self, each_handle_count: u32) -> Self {
+        self.params.each_handle_count = each_handle_count;
+        self
+    }
+    pub fn send_ask_interval(mut self, send_ask_interval: u32) -> Self {
+        self.params.send_ask_interval = send_ask_interval;
+        self
+    }
+    pub fn send_message_delay_range(mut self, range: (u32, u32)) -> Self {
+        self.params.send_message_delay_range = range;
+        self
+    }
+    pub fn send_hash_delay_range(mut self, range: (u32, u32)) -> Self {
+        self.params.send_hash_delay_range = range;
+        self
+    }
+    pub fn send_ask_for_delay_range(mut self, range: (u32, u32)) -> Self {
+        self.params.send_ask_for_delay_range = range;
+        self
+    }
+    pub fn send_reply_ask_delay_range(mut self, range: (u32, u32)) -> Self {
+        self.params.send_reply_ask_delay_range = range;
+        self
+    }
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.params.seed = seed;
+        self
+    }
+    pub fn topology(mut self, topology: TopologyKind) -> Self {
+        self.params.topology = topology;
+        self
+    }
+    pub fn latency(mut self, latency: LatencyKind) -> Self {
+        self.params.latency = latency;
+        self
+    }
+    pub fn payload_size(mut self, payload_size: u32) -> Self {
+        self.params.payload_size = payload_size;
+        self
+    }
+    pub fn upload_bandwidth(mut self, upload_bandwidth: u32) -> Self {
+        self.params.upload_bandwidth = upload_bandwidth;
+        self
+    }
+    pub fn download_bandwidth(mut self, download_bandwidth: u32) -> Self {
+        self.params.download_bandwidth = download_bandwidth;
+        self
+    }
+    pub fn bloom_filter(mut self, bloom_filter: BloomKind) -> Self {
+        self.params.bloom_filter = bloom_filter;
+        self
+    }
+    pub fn offline_fraction(mut self, offline_fraction: f64) -> Self {
+        self.params.offline_fraction = offline_fraction;
+        self
+    }
+    pub fn churn_rate(mut self, churn_rate: f64) -> Self {
+        self.params.churn_rate = churn_rate;
+        self
+    }
+    pub fn packet_loss(mut self, packet_loss: LossKind) -> Self {
+        self.params.packet_loss = packet_loss;
+        self
+    }
+    pub fn partitions(mut self, partitions: PartitionKind) -> Self {
+        self.params.partitions = partitions;
+        self
+    }
+    pub fn behaviours(mut self, behaviours: BehaviourMix) -> Self {
+        self.params.behaviours = behaviours;
+        self
+    }
+    pub fn source(mut self, source: SourceKind) -> Self {
+        self.params.source = source;
+        self
+    }
+    pub fn arrival_rate(mut self, arrival_rate: f64) -> Self {
+        self.params.arrival_rate = arrival_rate;
+        self
+    }
+    pub fn protocol(mut self, protocol: ProtocolKind) -> Self {
+        self.params.protocol = protocol;
+        self
+    }
+
+    pub fn build(self) -> Result<ParamsPacket, ParamsError> {
+        self.params.validate()?;
+        Ok(self.params)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamsError {
+    NodeSizeTooSmall(u32),
+    InvalidT {
+        t: u32,
+        node_size: u32,
+    },
+    ZeroMessageCount,
+    ZeroMaxHopNum,
+    KExceedsMaxHopNum {
+        k: u32,
+        max_hop_num: u32,
+    },
+    EmptyDelayRange {
+        name: &'static str,
+        range: (u32, u32),
+    },
+    InvalidTopology(String),
+    InvalidLatency(String),
+    InvalidBloomFilter(String),
+    InvalidFailureModel(String),
+    InvalidPacketLoss(String),
+    InvalidPartitions(String),
+    InvalidBehaviours(String),
+    InvalidSource(String),
+    InvalidArrivalRate(String),
+    InvalidProtocol(String),
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamsError::NodeSizeTooSmall(node_size) => {
+                write!(f, "node_size must be at least 2, got {}", node_size)
+            }
+            ParamsError::InvalidT { t, node_size } => write!(
+                f,
+                "t must be in 1..node_size, got t={} node_size={}",
+                t, node_size
+            ),
+            ParamsError::ZeroMessageCount => write!(f, "n must be at least 1"),
+            ParamsError::ZeroMaxHopNum => write!(f, "max_hop_num must be at least 1"),
+            ParamsError::KExceedsMaxHopNum { k, max_hop_num } => write!(
+                f,
+                "k must not exceed max_hop_num, got k={} max_hop_num={}",
+                k, max_hop_num
+            ),
+            ParamsError::EmptyDelayRange { name, range } => write!(
+                f,
+                "{} must satisfy lo < hi, got ({}, {})",
+                name, range.0, range.1
+            ),
+            ParamsError::InvalidTopology(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidLatency(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidBloomFilter(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidFailureModel(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidPacketLoss(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidPartitions(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidBehaviours(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidSource(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidArrivalRate(reason) => write!(f, "{}", reason),
+            ParamsError::InvalidProtocol(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+/// flat, serializable view of [`ParamsPacket`] used as key columns of csv/json records.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ParamsRecord {
+    node_size: u32,
+    t: u32,
+    k: u32,
+    n: u32,
+    seed: u64,
+    max_hop_num: u32,
+    each_handle_count: u32,
+    send_ask_interval: u32,
+    send_message_delay_lo: u32,
+    send_message_delay_hi: u32,
+    send_hash_delay_lo: u32,
+    send_hash_delay_hi: u32,
+    send_ask_for_delay_lo: u32,
+    send_ask_for_delay_hi: u32,
+    send_reply_ask_delay_lo: u32,
+    send_reply_ask_delay_hi: u32,
+    topology: String,
+    latency: String,
+    payload_size: u32,
+    upload_bandwidth: u32,
+    download_bandwidth: u32,
+    bloom_filter: String,
+    offline_fraction: f64,
+    churn_rate: f64,
+    packet_loss: String,
+    partitions: String,
+    behaviours: String,
+    source: String,
+    arrival_rate: f64,
+    protocol: String,
+}
+
+impl From<&ParamsPacket> for ParamsRecord {
+    fn from(p: &ParamsPacket) -> Self {
+        ParamsRecord {
+            node_size: p.node_size,
+            t: p.t,
+            k: p.k,
+            n: p.n,
+            seed: p.seed,
+            max_hop_num: p.max_hop_num,
+            each_handle_count: p.each_handle_count,
+            send_ask_interval: p.send_ask_interval,
+            send_message_delay_lo: p.send_message_delay_range.0,
+            send_message_delay_hi: p.send_message_delay_range.1,
+            send_hash_delay_lo: p.send_hash_delay_range.0,
+            send_hash_delay_hi: p.send_hash_delay_range.1,
+            send_ask_for_delay_lo: p.send_ask_for_delay_range.0,
+            send_ask_for_delay_hi: p.send_ask_for_delay_range.1,
+            send_reply_ask_delay_lo: p.send_reply_ask_delay_range.0,
+            send_reply_ask_delay_hi: p.send_reply_ask_delay_range.1,
+            topology: p.topology.to_string(),
+            latency: p.latency.to_string(),
+            payload_size: p.payload_size,
+            upload_bandwidth: p.upload_bandwidth,
+            download_bandwidth: p.download_bandwidth,
+            bloom_filter: p.bloom_filter.to_string(),
+            offline_fraction: p.offline_fraction,
+            churn_rate: p.churn_rate,
+            packet_loss: p.packet_loss.to_string(),
+            partitions: p.partitions.to_string(),
+            behaviours: p.behaviours.to_string(),
+            source: p.source.to_string(),
+            arrival_rate: p.arrival_rate,
+            protocol: p.protocol.to_string(),
+        }
+    }
+}
+
+/// show a delay range the way it is reported in result tables.
+pub fn format_range(range: (u32, u32)) -> String {
+    format!("{}-{}", range.0, range.1)
+}
+
+/// parse a delay range written as `lo-hi`, the inverse of [`format_range`].
+pub fn parse_range(s: &str) -> Result<(u32, u32), String> {
+    let (lo, hi) = s
+        .split_once('-')
+        .ok_or_else(|| format!("expect range as `lo-hi`, got `{}`", s))?;
+    let lo = lo.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    let hi = hi.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    Ok((lo, hi))
+}
+
+#[test]
+fn test_default_params_valid() {
+    assert_eq!(ParamsPacket::new(100, 3, 4, 100).validate(), Ok(()));
+    assert_eq!(
+        ParamsPacket::builder(100, 3, 4, 100).build(),
+        Ok(ParamsPacket::new(100, 3, 4, 100))
+    );
+}
+
+#[test]
+fn test_parse_range() {
+    assert_eq!(parse_range("100-120"), Ok((100, 120)));
+    assert_eq!(parse_range(&format_range((5, 9))), Ok((5, 9)));
+    assert!(parse_range("100").is_err());
+    assert!(parse_range("a-1").is_err());
+}
+
+#[test]
+fn test_builder_validation() {
+    let p = ParamsPacket::builder(100, 3, 4, 100)
+        .max_hop_num(12)
+        .send_hash_delay_range((50, 60))
+        .build()
+        .unwrap();
+    assert_eq!(p.max_hop_num(), 12);
+    assert_eq!(p.send_hash_delay_range(), (50, 60));
+
+    assert_eq!(
+        ParamsPacket::builder(10, 10, 4, 100).build(),
+        Err(ParamsError::InvalidT {
+            t: 10,
+            node_size: 10
+        })
+    );
+    assert_eq!(
+        ParamsPacket::builder(100, 3, 11, 100).build(),
+        Err(ParamsError::KExceedsMaxHopNum {
+            k: 11,
+            max_hop_num: 10
+        })
+    );
+    assert!(matches!(
+        ParamsPacket::builder(100, 3, 4, 100)
+            .send_ask_for_delay_range((30, 30))
+            .build(),
+        Err(ParamsError::EmptyDelayRange { .. })
+    ));
+    for churn_rate in [f64::NAN, -1.0, f64::INFINITY, 1e300] {
+        assert!(matches!(
+            ParamsPacket::builder(100, 3, 4, 100)
+                .churn_rate(churn_rate)
+                .build(),
+            Err(ParamsError::InvalidFailureModel(_))
+        ));
+    }
+    assert!(ParamsPacket::builder(100, 3, 4, 100)
+        .churn_rate(MAX_CHURN_RATE)
+        .build()
+        .is_ok());
+}
@@ -0,0 +1,403 @@
+use std::{fmt, fs, str::FromStr, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    message::{counter_of, MessageId, NodeId},
+    message_queue::TimeStamp,
+};
+
+/// Network partitions applied while a message spreads.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub enum PartitionKind {
+    /// every node can reach every other.
+    #[default]
+    None,
+    /// nodes split into `groups` equal ranges of node id from `start` until `heal`.
+    /// Until it heals, a message only reaches the group its source is in.
+    Split {
+        groups: u32,
+        start: TimeStamp,
+        heal: TimeStamp,
+    },
+    /// partitions read from a toml file.
+    Schedule(Arc<PartitionSchedule>),
+}
+
+impl PartitionKind {
+    pub fn validate(&self, node_size: u32) -> Result<(), String> {
+        match self {
+            PartitionKind::None => Ok(()),
+            PartitionKind::Split {
+                groups,
+                start,
+                heal,
+            } => {
+                if *groups < 2 || *groups > node_size {
+                    Err(format!(
+                        "{}: groups must be in 2..={} (node_size)",
+                        self, node_size
+                    ))
+                } else if start >= heal {
+                    Err(format!("{}: start must be before heal", self))
+                } else {
+                    Ok(())
+                }
+            }
+            PartitionKind::Schedule(schedule) => schedule.validate(node_size),
+        }
+    }
+
+    pub fn build(&self, node_size: usize) -> Partitions {
+        let partitions = match self {
+            PartitionKind::None => vec![],
+            PartitionKind::Split {
+                groups,
+                start,
+                heal,
+            } => vec![ActivePartition {
+                start: *start,
+                heal: *heal,
+                group_of: (0..node_size)
+                    .map(|node| node * *groups as usize / node_size)
+                    .collect(),
+                group_count: *groups as usize,
+            }],
+            PartitionKind::Schedule(schedule) => schedule
+                .partitions
+                .iter()
+                .map(|p| p.resolve(node_size))
+                .collect(),
+        };
+        Partitions {
+            partitions,
+            drop_count: Vec::new(),
+        }
+    }
+}
+
+/// `none`, `split:<groups>:<start>:<heal>` or `file:<path>`; files are loaded while parsing.
+impl FromStr for PartitionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(PartitionKind::Schedule(Arc::new(
+                PartitionSchedule::from_file(path)?,
+            )));
+        }
+        let parts: Vec<&str> = s.split(':').collect();
+        let parse = |v: &str| v.parse::<u32>().map_err(|e| format!("{} in `{}`", e, s));
+        match parts.as_slice() {
+            ["none"] => Ok(PartitionKind::None),
+            ["split", groups, start, heal] => Ok(PartitionKind::Split {
+                groups: parse(groups)?,
+                start: parse(start)?,
+                heal: parse(heal)?,
+            }),
+            _ => Err(format!(
+                "unknown partitions `{}`, expect none, split:<groups>:<start>:<heal> or file:<path>",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for PartitionKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for PartitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartitionKind::None => write!(f, "none"),
+            PartitionKind::Split {
+                groups,
+                start,
+                heal,
+            } => write!(f, "split:{}:{}:{}", groups, start, heal),
+            PartitionKind::Schedule(schedule) => write!(f, "file:{}", schedule.source),
+        }
+    }
+}
+
+/// One partition of a schedule file.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Partition {
+    pub start: TimeStamp,
+    pub heal: TimeStamp,
+    /// nodes in none of these groups make one more group.
+    pub groups: Vec<Vec<NodeId>>,
+}
+
+impl Partition {
+    fn resolve(&self, node_size: usize) -> ActivePartition {
+        let rest = self.groups.len();
+        let mut group_of = vec![rest; node_size];
+        for (group, nodes) in self.groups.iter().enumerate() {
+            nodes.iter().for_each(|node| group_of[*node] = group);
+        }
+        ActivePartition {
+            start: self.start,
+            heal: self.heal,
+            group_count: if group_of.contains(&rest) {
+                rest + 1
+            } else {
+                rest
+            },
+            group_of,
+        }
+    }
+}
+
+/// Partitions that appear and heal over time, read from toml. They may overlap, two nodes
+/// can talk only if they are in the same group of every partition in effect:
+///
+/// ```toml
+/// [[partitions]]
+/// start = 200
+/// heal = 1200
+/// groups = [[0, 1, 2, 3], [4, 5, 6]]
+/// ```
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct PartitionSchedule {
+    #[serde(skip)]
+    source: String,
+    partitions: Vec<Partition>,
+}
+
+impl PartitionSchedule {
+    pub fn from_file(path: &str) -> Result<PartitionSchedule, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        PartitionSchedule::parse(path, &content)
+    }
+
+    pub fn parse(source: &str, content: &str) -> Result<PartitionSchedule, String> {
+        let mut schedule: PartitionSchedule =
+            toml::from_str(content).map_err(|e| format!("{}: {}", source, e))?;
+        schedule.source = source.to_string();
+        for p in &schedule.partitions {
+            if p.start >= p.heal {
+                return Err(format!(
+                    "{}: partition start {} must be before heal {}",
+                    source, p.start, p.heal
+                ));
+            }
+            let mut nodes: Vec<NodeId> = p.groups.iter().flatten().copied().collect();
+            nodes.sort_unstable();
+            if nodes.windows(2).any(|w| w[0] == w[1]) {
+                return Err(format!(
+                    "{}: a node is in more than one group of a partition",
+                    source
+                ));
+            }
+        }
+        Ok(schedule)
+    }
+
+    pub fn validate(&self, node_size: u32) -> Result<(), String> {
+        let max_node = self
+            .partitions
+            .iter()
+            .flat_map(|p| p.groups.iter().flatten())
+            .max();
+        match max_node {
+            Some(node) if *node >= node_size as usize => Err(format!(
+                "{}: node {} out of node_size {}",
+                self.source, node, node_size
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ActivePartition {
+    start: TimeStamp,
+    heal: TimeStamp,
+    group_of: Vec<usize>,
+    group_count: usize,
+}
+
+impl ActivePartition {
+    fn in_effect(&self, ts: TimeStamp) -> bool {
+        self.start <= ts && ts < self.heal
+    }
+}
+
+/// Which nodes can reach each other at a given ts. Built once per run.
+#[derive(Debug, Clone)]
+pub struct Partitions {
+    partitions: Vec<ActivePartition>,
+    /// sends of each message id dropped because `from` and `to` were partitioned.
+    drop_count: Vec<u32>,
+}
+
+/// How far the message got in every group of one partition before it healed.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PartitionReach {
+    pub start: TimeStamp,
+    pub heal: TimeStamp,
+    /// live nodes of each group.
+    pub group_size: Vec<u32>,
+    /// live nodes of each group that recvd full message before heal.
+    pub recv_before_heal: Vec<u32>,
+}
+
+impl PartitionReach {
+    /// groups with at least one node that recvd full message before heal.
+    pub fn reached_group_count(&self) -> u32 {
+        self.recv_before_heal.iter().filter(|n| **n > 0).count() as u32
+    }
+}
+
+impl Partitions {
+    pub fn drop_count(&self, message_id: MessageId) -> u32 {
+        self.drop_count.get(message_id).copied().unwrap_or(0)
+    }
+
+    pub fn reset(&mut self) {
+        self.drop_count.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.partitions.is_empty()
+    }
+
+    /// if a message sent at `ts` gets from `from` to `to`.
+    pub fn can_reach(&self, from: NodeId, to: NodeId, ts: TimeStamp) -> bool {
+        self.partitions
+            .iter()
+            .filter(|p| p.in_effect(ts))
+            .all(|p| p.group_of[from] == p.group_of[to])
+    }
+
+    pub fn record_drop(&mut self, message_id: MessageId) {
+        *counter_of(&mut self.drop_count, message_id) += 1;
+    }
+
+    /// when the last partition heals, `None` without partitions.
+    pub fn last_heal_ts(&self) -> Option<TimeStamp> {
+        self.partitions.iter().map(|p| p.heal).max()
+    }
+
+    /// reach of every partition, given when each node first recvd full message and if it is live.
+    pub fn reach(&self, recv_ts: &[Option<TimeStamp>], live: &[bool]) -> Vec<PartitionReach> {
+        self.partitions
+            .iter()
+            .map(|p| {
+                let mut group_size = vec![0; p.group_count];
+                let mut recv_before_heal = vec![0; p.group_count];
+                for (node, group) in p.group_of.iter().enumerate() {
+                    if !live[node] {
+                        continue;
+                    }
+                    group_size[*group] += 1;
+                    if recv_ts[node].is_some_and(|ts| ts < p.heal) {
+                        recv_before_heal[*group] += 1;
+                    }
+                }
+                PartitionReach {
+                    start: p.start,
+                    heal: p.heal,
+                    group_size,
+                    recv_before_heal,
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_parse_partition_kind() {
+    assert_eq!("none".parse(), Ok(PartitionKind::None));
+    assert_eq!(
+        "split:2:100:600".parse(),
+        Ok(PartitionKind::Split {
+            groups: 2,
+            start: 100,
+            heal: 600
+        })
+    );
+    assert_eq!(
+        "split:2:100:600"
+            .parse::<PartitionKind>()
+            .unwrap()
+            .to_string(),
+        "split:2:100:600"
+    );
+    assert!("split:2:100".parse::<PartitionKind>().is_err());
+    assert!("split:1:0:100"
+        .parse::<PartitionKind>()
+        .unwrap()
+        .validate(10)
+        .is_err());
+    assert!("split:2:100:100"
+        .parse::<PartitionKind>()
+        .unwrap()
+        .validate(10)
+        .is_err());
+
+    let schedule = PartitionSchedule::parse(
+        "test",
+        "[[partitions]]\nstart = 0\nheal = 100\ngroups = [[0, 1], [2]]\n",
+    )
+    .unwrap();
+    assert!(schedule.validate(3).is_ok());
+    assert!(schedule.validate(2).is_err());
+    assert!(PartitionSchedule::parse(
+        "test",
+        "[[partitions]]\nstart = 0\nheal = 100\ngroups = [[0, 1], [1]]\n",
+    )
+    .is_err());
+}
+
+#[test]
+fn test_partitions_reach() {
+    let schedule = PartitionSchedule::parse(
+        "test",
+        "[[partitions]]\nstart = 0\nheal = 100\ngroups = [[0, 1], [2, 3]]\n\n\
+         [[partitions]]\nstart = 50\nheal = 200\ngroups = [[0]]\n",
+    )
+    .unwrap();
+    let mut partitions = PartitionKind::Schedule(Arc::new(schedule)).build(5);
+    // node 4 is the rest group of the first partition
+    assert!(partitions.can_reach(0, 1, 10));
+    assert!(!partitions.can_reach(0, 2, 10));
+    assert!(!partitions.can_reach(1, 4, 10));
+    // second partition cuts node 0 off from 50 on
+    assert!(!partitions.can_reach(0, 1, 50));
+    assert!(partitions.can_reach(1, 2, 150));
+    assert!(partitions.can_reach(0, 3, 200));
+    assert_eq!(partitions.last_heal_ts(), Some(200));
+
+    partitions.record_drop(1);
+    assert_eq!(partitions.drop_count(1), 1);
+    assert_eq!(partitions.drop_count(0), 0);
+    partitions.reset();
+    assert_eq!(partitions.drop_count(1), 0);
+
+    let recv_ts = [Some(0), Some(30), Some(120), None, Some(90)];
+    let live = [true, true, true, true, false];
+    let reach = partitions.reach(&recv_ts, &live);
+    assert_eq!(reach[0].group_size, vec![2, 2, 0]);
+    assert_eq!(reach[0].recv_before_heal, vec![2, 0, 0]);
+    assert_eq!(reach[0].reached_group_count(), 1);
+    assert_eq!(reach[1].group_size, vec![1, 3]);
+    assert_eq!(reach[1].recv_before_heal, vec![1, 2]);
+
+    let split = PartitionKind::Split {
+        groups: 3,
+        start: 0,
+        heal: 10,
+    }
+    .build(7);
+    assert!(split.can_reach(0, 2, 0));
+    assert!(!split.can_reach(2, 3, 0));
+    assert!(split.can_reach(2, 3, 10));
+}
@@ -1,4 +1,17 @@
-use crate::{message_queue::MessageQueue, node_status::NodeStatus, rrs_simulator::ParamsPacket};
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    behaviour::Behaviour,
+    message::{MessageId, NodeId},
+    message_queue::TimeStamp,
+    network::{Broadcast, Network},
+    params_packet::{format_range, ParamsPacket, ParamsRecord},
+    partition::PartitionReach,
+    statistics::{percentile, Summary},
+};
 
 pub struct ResultPack {
     params: ParamsPacket,
@@ -14,30 +27,329 @@ impl ResultPack {
     }
 
     pub fn show(&self) {
-        let mut avg_recv_node_size: f64 = 0.0;
-        let mut avg_send_msg_count: f64 = 0.0;
-        let mut avg_send_hash_count: f64 = 0.0;
-        let mut avg_send_ask_for_count: f64 = 0.0;
-        self.each_result_data.iter().for_each(|e| {
-            avg_recv_node_size += e.recv_node_size as f64;
-            avg_send_msg_count += e.send_message_count as f64;
-            avg_send_hash_count += e.send_hash_count as f64;
-            avg_send_ask_for_count += e.send_ask_for_count as f64;
-        });
-        log::info!(
-            "|N|t|k|n|avg recv node size|avg send message count|avg send hash count|avg send ask for count|",
-        );
-        log::info!(
-            "|{} | {} | {} | {} | {} | {} | {} | {} |",
+        log::info!("{}", ResultPack::markdown_header());
+        log::info!("{}", self.markdown_row());
+    }
+
+    pub fn markdown_header() -> &'static str {
+        "|N|t|k|n|seed|max hop num|each handle count|send ask interval|send message delay|send hash delay|send ask for delay|send reply ask delay|topology|latency|payload size|upload bandwidth|download bandwidth|bloom filter|offline fraction|churn rate|packet loss|partitions|behaviours|source|arrival rate|protocol|avg recv node size|avg send message count|avg send hash count|avg send ask for count|avg total bytes|avg payload bytes|avg hash bytes|avg bloomstatus bytes|avg header bytes|avg mean node sent bytes|avg p50 node sent bytes|avg p95 node sent bytes|avg max node sent bytes|avg mean node recv bytes|avg p50 node recv bytes|avg p95 node recv bytes|avg max node recv bytes|avg bloom skip count|avg false positive skip count|avg live node size|avg offline drop count|avg crash count|avg join count|avg lost message count|avg lost hash count|avg lost ask for count|avg partition drop count|avg reached group count|avg heal recovery time|avg honest recv node size|avg honest live node size|avg swallowed count|avg unanswered ask for count|avg lie count|avg spam count|avg would merge count|avg send control count|avg lost control count|avg partial node size|avg mean rebuild delay|avg time to 50%|avg time to 90%|avg time to 99%|avg time to 100%|avg mean latency|avg p50 latency|avg p90 latency|avg p99 latency|full coverage ratio|recv node size 95% CI|send message count 95% CI|send hash count 95% CI|send ask for count 95% CI|mean latency 95% CI|"
+    }
+
+    pub fn markdown_row(&self) -> String {
+        format!(
+            "|{} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} | {} |",
             self.params.node_size(),
             self.params.t(),
             self.params.k(),
             self.params.n(),
-            avg_recv_node_size / self.each_result_data.len() as f64,
-            avg_send_msg_count / self.each_result_data.len() as f64,
-            avg_send_hash_count / self.each_result_data.len() as f64,
-            avg_send_ask_for_count / self.each_result_data.len() as f64,
-        );
+            self.params.seed(),
+            self.params.max_hop_num(),
+            self.params.each_handle_count(),
+            self.params.send_ask_interval(),
+            format_range(self.params.send_message_delay_range()),
+            format_range(self.params.send_hash_delay_range()),
+            format_range(self.params.send_ask_for_delay_range()),
+            format_range(self.params.send_reply_ask_delay_range()),
+            self.params.topology(),
+            self.params.latency(),
+            self.params.payload_size(),
+            self.params.upload_bandwidth(),
+            self.params.download_bandwidth(),
+            self.params.bloom_filter(),
+            self.params.offline_fraction(),
+            self.params.churn_rate(),
+            self.params.packet_loss(),
+            self.params.partitions(),
+            self.params.behaviours(),
+            self.params.source(),
+            self.params.arrival_rate(),
+            self.params.protocol(),
+            self.avg_recv_node_size(),
+            self.avg_send_message_count(),
+            self.avg_send_hash_count(),
+            self.avg_send_ask_for_count(),
+            self.average(|e| e.total_bytes as f64),
+            self.average(|e| e.payload_bytes as f64),
+            self.average(|e| e.hash_bytes as f64),
+            self.average(|e| e.bloomstatus_bytes as f64),
+            self.average(|e| e.header_bytes as f64),
+            self.average(|e| e.mean_node_sent_bytes),
+            self.average(|e| e.p50_node_sent_bytes as f64),
+            self.average(|e| e.p95_node_sent_bytes as f64),
+            self.average(|e| e.max_node_sent_bytes as f64),
+            self.average(|e| e.mean_node_recv_bytes),
+            self.average(|e| e.p50_node_recv_bytes as f64),
+            self.average(|e| e.p95_node_recv_bytes as f64),
+            self.average(|e| e.max_node_recv_bytes as f64),
+            self.average(|e| e.bloom_skip_count as f64),
+            self.average(|e| e.false_positive_skip_count as f64),
+            self.avg_live_node_size(),
+            self.average(|e| e.offline_drop_count as f64),
+            self.average(|e| e.crash_count as f64),
+            self.average(|e| e.join_count as f64),
+            self.average(|e| e.lost_message_count as f64),
+            self.average(|e| e.lost_hash_count as f64),
+            self.average(|e| e.lost_ask_for_count as f64),
+            self.average(|e| e.partition_drop_count as f64),
+            self.average(|e| e.reached_group_count as f64),
+            format_optional(self.avg_coverage_ts(|e| e.heal_recovery_ts)),
+            self.average(|e| e.honest_recv_node_size as f64),
+            self.average(|e| e.honest_live_node_size as f64),
+            self.average(|e| e.swallowed_count as f64),
+            self.average(|e| e.unanswered_ask_for_count as f64),
+            self.average(|e| e.lie_count as f64),
+            self.average(|e| e.spam_count as f64),
+            self.average(|e| e.would_merge_count as f64),
+            self.average(|e| e.send_control_count as f64),
+            self.average(|e| e.lost_control_count as f64),
+            self.average(|e| e.partial_node_size as f64),
+            format_optional(self.average_optional(|e| e.mean_rebuild_delay)),
+            format_optional(self.avg_coverage_ts(|e| e.coverage_50_ts)),
+            format_optional(self.avg_coverage_ts(|e| e.coverage_90_ts)),
+            format_optional(self.avg_coverage_ts(|e| e.coverage_99_ts)),
+            format_optional(self.avg_coverage_ts(|e| e.coverage_100_ts)),
+            format_optional(self.average_optional(|e| e.mean_latency)),
+            format_optional(self.avg_coverage_ts(|e| e.p50_latency)),
+            format_optional(self.avg_coverage_ts(|e| e.p90_latency)),
+            format_optional(self.avg_coverage_ts(|e| e.p99_latency)),
+            self.full_coverage_ratio(),
+            format_mean_ci(self.summary_of("recv_node_size")),
+            format_mean_ci(self.summary_of("send_message_count")),
+            format_mean_ci(self.summary_of("send_hash_count")),
+            format_mean_ci(self.summary_of("send_ask_for_count")),
+            format_mean_ci(self.summary_of("mean_latency")),
+        )
+    }
+
+    /// fraction of messages that reached every live node.
+    pub fn full_coverage_ratio(&self) -> f64 {
+        self.average(|e| (e.recv_node_size == e.live_node_size) as u32 as f64)
+    }
+
+    /// statistics of every metric in [`ResultData::metrics`] over all messages.
+    pub fn metric_summaries(&self) -> Vec<(&'static str, Option<Summary>)> {
+        let metrics: Vec<Vec<(&'static str, Option<f64>)>> =
+            self.each_result_data.iter().map(|e| e.metrics()).collect();
+        ResultData::metric_names()
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let values: Vec<f64> = metrics.iter().filter_map(|m| m[index].1).collect();
+                (*name, Summary::from_values(&values))
+            })
+            .collect()
+    }
+
+    pub fn summary_of(&self, metric_name: &str) -> Option<Summary> {
+        let values: Vec<f64> = self
+            .each_result_data
+            .iter()
+            .filter_map(|e| {
+                e.metrics()
+                    .into_iter()
+                    .find(|(name, _)| *name == metric_name)
+                    .and_then(|(_, value)| value)
+            })
+            .collect();
+        Summary::from_values(&values)
+    }
+
+    /// one record of this parameter set, for csv/json output.
+    pub fn summary_record(&self) -> SummaryRecord {
+        SummaryRecord {
+            params: ParamsRecord::from(&self.params),
+            avg_recv_node_size: self.avg_recv_node_size(),
+            avg_send_message_count: self.avg_send_message_count(),
+            avg_send_hash_count: self.avg_send_hash_count(),
+            avg_send_ask_for_count: self.avg_send_ask_for_count(),
+            avg_total_bytes: self.average(|e| e.total_bytes as f64),
+            avg_payload_bytes: self.average(|e| e.payload_bytes as f64),
+            avg_hash_bytes: self.average(|e| e.hash_bytes as f64),
+            avg_bloomstatus_bytes: self.average(|e| e.bloomstatus_bytes as f64),
+            avg_header_bytes: self.average(|e| e.header_bytes as f64),
+            avg_mean_node_sent_bytes: self.average(|e| e.mean_node_sent_bytes),
+            avg_p50_node_sent_bytes: self.average(|e| e.p50_node_sent_bytes as f64),
+            avg_p95_node_sent_bytes: self.average(|e| e.p95_node_sent_bytes as f64),
+            avg_max_node_sent_bytes: self.average(|e| e.max_node_sent_bytes as f64),
+            avg_mean_node_recv_bytes: self.average(|e| e.mean_node_recv_bytes),
+            avg_p50_node_recv_bytes: self.average(|e| e.p50_node_recv_bytes as f64),
+            avg_p95_node_recv_bytes: self.average(|e| e.p95_node_recv_bytes as f64),
+            avg_max_node_recv_bytes: self.average(|e| e.max_node_recv_bytes as f64),
+            avg_bloom_skip_count: self.average(|e| e.bloom_skip_count as f64),
+            avg_false_positive_skip_count: self.average(|e| e.false_positive_skip_count as f64),
+            avg_live_node_size: self.avg_live_node_size(),
+            avg_offline_drop_count: self.average(|e| e.offline_drop_count as f64),
+            avg_crash_count: self.average(|e| e.crash_count as f64),
+            avg_join_count: self.average(|e| e.join_count as f64),
+            avg_lost_message_count: self.average(|e| e.lost_message_count as f64),
+            avg_lost_hash_count: self.average(|e| e.lost_hash_count as f64),
+            avg_lost_ask_for_count: self.average(|e| e.lost_ask_for_count as f64),
+            avg_partition_drop_count: self.average(|e| e.partition_drop_count as f64),
+            avg_reached_group_count: self.average(|e| e.reached_group_count as f64),
+            avg_heal_recovery_ts: self.avg_coverage_ts(|e| e.heal_recovery_ts),
+            avg_honest_recv_node_size: self.average(|e| e.honest_recv_node_size as f64),
+            avg_honest_live_node_size: self.average(|e| e.honest_live_node_size as f64),
+            avg_swallowed_count: self.average(|e| e.swallowed_count as f64),
+            avg_unanswered_ask_for_count: self.average(|e| e.unanswered_ask_for_count as f64),
+            avg_lie_count: self.average(|e| e.lie_count as f64),
+            avg_spam_count: self.average(|e| e.spam_count as f64),
+            avg_would_merge_count: self.average(|e| e.would_merge_count as f64),
+            avg_send_control_count: self.average(|e| e.send_control_count as f64),
+            avg_lost_control_count: self.average(|e| e.lost_control_count as f64),
+            avg_partial_node_size: self.average(|e| e.partial_node_size as f64),
+            avg_mean_rebuild_delay: self.average_optional(|e| e.mean_rebuild_delay),
+            avg_coverage_50_ts: self.avg_coverage_ts(|e| e.coverage_50_ts),
+            avg_coverage_90_ts: self.avg_coverage_ts(|e| e.coverage_90_ts),
+            avg_coverage_99_ts: self.avg_coverage_ts(|e| e.coverage_99_ts),
+            avg_coverage_100_ts: self.avg_coverage_ts(|e| e.coverage_100_ts),
+            avg_mean_latency: self.average_optional(|e| e.mean_latency),
+            avg_p50_latency: self.avg_coverage_ts(|e| e.p50_latency),
+            avg_p90_latency: self.avg_coverage_ts(|e| e.p90_latency),
+            avg_p99_latency: self.avg_coverage_ts(|e| e.p99_latency),
+            full_coverage_ratio: self.full_coverage_ratio(),
+            stats: self.stats_fields(),
+        }
+    }
+
+    /// `<metric>_<stat>` fields of every metric summary, mean excluded (it is the `avg_` field).
+    fn stats_fields(&self) -> Map<String, Value> {
+        let mut fields = Map::new();
+        for (name, summary) in self.metric_summaries() {
+            let stats = [
+                ("std_dev", summary.as_ref().map(|s| s.std_dev)),
+                ("min", summary.as_ref().map(|s| s.min)),
+                ("max", summary.as_ref().map(|s| s.max)),
+                ("median", summary.as_ref().map(|s| s.median)),
+                ("p5", summary.as_ref().map(|s| s.p5)),
+                ("p25", summary.as_ref().map(|s| s.p25)),
+                ("p75", summary.as_ref().map(|s| s.p75)),
+                ("p95", summary.as_ref().map(|s| s.p95)),
+                ("ci95_lo", summary.as_ref().map(|s| s.ci95_lo)),
+                ("ci95_hi", summary.as_ref().map(|s| s.ci95_hi)),
+            ];
+            for (stat, value) in stats {
+                fields.insert(format!("{}_{}", name, stat), Value::from(value));
+            }
+        }
+        fields
+    }
+
+    /// one record of each simulated message, for csv/json output.
+    pub fn message_records(&self) -> Vec<MessageRecord<'_>> {
+        let params = ParamsRecord::from(&self.params);
+        self.each_result_data
+            .iter()
+            .enumerate()
+            .map(|(message_index, data)| MessageRecord {
+                params: params.clone(),
+                message_index,
+                data,
+            })
+            .collect()
+    }
+
+    pub fn avg_recv_node_size(&self) -> f64 {
+        self.average(|e| e.recv_node_size as f64)
+    }
+    pub fn avg_live_node_size(&self) -> f64 {
+        self.average(|e| e.live_node_size as f64)
+    }
+    pub fn avg_send_message_count(&self) -> f64 {
+        self.average(|e| e.send_message_count as f64)
+    }
+    pub fn avg_send_hash_count(&self) -> f64 {
+        self.average(|e| e.send_hash_count as f64)
+    }
+    pub fn avg_send_ask_for_count(&self) -> f64 {
+        self.average(|e| e.send_ask_for_count as f64)
+    }
+
+    fn average<F: Fn(&ResultData) -> f64>(&self, f: F) -> f64 {
+        self.each_result_data.iter().map(f).sum::<f64>() / self.each_result_data.len() as f64
+    }
+
+    /// average over the messages that have this metric, `None` if no message has.
+    fn average_optional<F: Fn(&ResultData) -> Option<f64>>(&self, f: F) -> Option<f64> {
+        let values: Vec<f64> = self.each_result_data.iter().filter_map(f).collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.iter().sum::<f64>() / values.len() as f64)
+        }
+    }
+
+    fn avg_coverage_ts<F: Fn(&ResultData) -> Option<TimeStamp>>(&self, f: F) -> Option<f64> {
+        self.average_optional(|e| f(e).map(|ts| ts as f64))
+    }
+
+    pub fn params(&self) -> &ParamsPacket {
+        &self.params
+    }
+
+    pub fn each_result_data(&self) -> &[ResultData] {
+        &self.each_result_data
+    }
+
+    /// results of each source apart, sorted by source.
+    pub fn by_source(&self) -> Vec<(NodeId, ResultPack)> {
+        let mut packs: BTreeMap<NodeId, ResultPack> = BTreeMap::new();
+        for data in &self.each_result_data {
+            packs
+                .entry(data.source)
+                .or_insert_with(|| ResultPack::new(&self.params))
+                .add_result(data.clone());
+        }
+        packs.into_iter().collect()
+    }
+
+    /// one record of each source of this parameter set, for a per source breakdown.
+    pub fn source_records(&self) -> Vec<SourceRecord> {
+        let params = ParamsRecord::from(&self.params);
+        self.by_source()
+            .into_iter()
+            .map(|(source, rp)| SourceRecord {
+                params: params.clone(),
+                source,
+                source_degree: rp.each_result_data[0].source_degree,
+                message_count: rp.each_result_data.len(),
+                avg_recv_node_size: rp.avg_recv_node_size(),
+                avg_live_node_size: rp.avg_live_node_size(),
+                full_coverage_ratio: rp.full_coverage_ratio(),
+                avg_coverage_100_ts: rp.avg_coverage_ts(|e| e.coverage_100_ts),
+                avg_mean_latency: rp.average_optional(|e| e.mean_latency),
+                avg_total_bytes: rp.average(|e| e.total_bytes as f64),
+            })
+            .collect()
+    }
+
+    /// one record of each group of each partition of each simulated message, for a per group
+    /// breakdown of the reach.
+    pub fn partition_records(&self) -> Vec<PartitionRecord> {
+        let params = ParamsRecord::from(&self.params);
+        let mut records = Vec::new();
+        for (message_index, data) in self.each_result_data.iter().enumerate() {
+            for (partition, reach) in data.partition_reach.iter().enumerate() {
+                for (group, (group_size, recv_before_heal)) in reach
+                    .group_size
+                    .iter()
+                    .zip(&reach.recv_before_heal)
+                    .enumerate()
+                {
+                    records.push(PartitionRecord {
+                        params: params.clone(),
+                        message_index,
+                        source_node: data.source,
+                        partition,
+                        start: reach.start,
+                        heal: reach.heal,
+                        group,
+                        group_size: *group_size,
+                        recv_before_heal: *recv_before_heal,
+                    });
+                }
+            }
+        }
+        records
     }
 
     fn add_result(&mut self, rd: ResultData) {
@@ -45,34 +357,847 @@ impl ResultPack {
     }
 }
 
-struct ResultData {
+#[derive(Serialize, Debug, Clone)]
+pub struct SourceRecord {
+    #[serde(flatten)]
+    params: ParamsRecord,
+    source: NodeId,
+    source_degree: u32,
+    message_count: usize,
+    avg_recv_node_size: f64,
+    avg_live_node_size: f64,
+    full_coverage_ratio: f64,
+    avg_coverage_100_ts: Option<f64>,
+    avg_mean_latency: Option<f64>,
+    avg_total_bytes: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PartitionRecord {
+    #[serde(flatten)]
+    params: ParamsRecord,
+    message_index: usize,
+    /// node that broadcast the message, apart from the `source` kind of the params.
+    source_node: NodeId,
+    /// index of the partition in the schedule, and when it was in effect.
+    partition: usize,
+    start: TimeStamp,
+    heal: TimeStamp,
+    /// live nodes of the group, and those that recvd full message before heal.
+    group: usize,
+    group_size: u32,
+    recv_before_heal: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SummaryRecord {
+    #[serde(flatten)]
+    params: ParamsRecord,
+    avg_recv_node_size: f64,
+    avg_send_message_count: f64,
+    avg_send_hash_count: f64,
+    avg_send_ask_for_count: f64,
+    avg_total_bytes: f64,
+    avg_payload_bytes: f64,
+    avg_hash_bytes: f64,
+    avg_bloomstatus_bytes: f64,
+    avg_header_bytes: f64,
+    avg_mean_node_sent_bytes: f64,
+    avg_p50_node_sent_bytes: f64,
+    avg_p95_node_sent_bytes: f64,
+    avg_max_node_sent_bytes: f64,
+    avg_mean_node_recv_bytes: f64,
+    avg_p50_node_recv_bytes: f64,
+    avg_p95_node_recv_bytes: f64,
+    avg_max_node_recv_bytes: f64,
+    avg_bloom_skip_count: f64,
+    avg_false_positive_skip_count: f64,
+    avg_live_node_size: f64,
+    avg_offline_drop_count: f64,
+    avg_crash_count: f64,
+    avg_join_count: f64,
+    avg_lost_message_count: f64,
+    avg_lost_hash_count: f64,
+    avg_lost_ask_for_count: f64,
+    avg_partition_drop_count: f64,
+    avg_reached_group_count: f64,
+    avg_heal_recovery_ts: Option<f64>,
+    avg_honest_recv_node_size: f64,
+    avg_honest_live_node_size: f64,
+    avg_swallowed_count: f64,
+    avg_unanswered_ask_for_count: f64,
+    avg_lie_count: f64,
+    avg_spam_count: f64,
+    avg_would_merge_count: f64,
+    avg_send_control_count: f64,
+    avg_lost_control_count: f64,
+    avg_partial_node_size: f64,
+    avg_mean_rebuild_delay: Option<f64>,
+    avg_coverage_50_ts: Option<f64>,
+    avg_coverage_90_ts: Option<f64>,
+    avg_coverage_99_ts: Option<f64>,
+    avg_coverage_100_ts: Option<f64>,
+    avg_mean_latency: Option<f64>,
+    avg_p50_latency: Option<f64>,
+    avg_p90_latency: Option<f64>,
+    avg_p99_latency: Option<f64>,
+    full_coverage_ratio: f64,
+    #[serde(flatten)]
+    stats: Map<String, Value>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct MessageRecord<'a> {
+    #[serde(flatten)]
+    params: ParamsRecord,
+    message_index: usize,
+    #[serde(flatten)]
+    data: &'a ResultData,
+}
+
+/// result of one simulated message.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ResultData {
+    /// sub seed of this message, replay it by `RRSSimulator::replay_message`.
+    message_seed: u64,
+    /// node that broadcast this message, and its degree in the topology.
+    source: NodeId,
+    source_degree: u32,
+    /// id of this message and ts it was sent at, both 0 unless messages spread concurrently.
+    message_id: MessageId,
+    start_ts: TimeStamp,
+    /// live nodes that recvd full message.
     recv_node_size: u32,
+    /// nodes online when the message stopped spreading, coverage is over these.
+    live_node_size: u32,
     send_message_count: u32,
     send_hash_count: u32,
     send_ask_for_count: u32,
+    /// bytes of every sent message, and by what they carry (see `WireBytes`).
+    total_bytes: u64,
+    payload_bytes: u64,
+    hash_bytes: u64,
+    bloomstatus_bytes: u64,
+    header_bytes: u64,
+    /// bytes of this message sent/recvd per node: mean, median, 95th percentile and most
+    /// over all nodes.
+    mean_node_sent_bytes: f64,
+    p50_node_sent_bytes: u64,
+    p95_node_sent_bytes: u64,
+    max_node_sent_bytes: u64,
+    mean_node_recv_bytes: f64,
+    p50_node_recv_bytes: u64,
+    p95_node_recv_bytes: u64,
+    max_node_recv_bytes: u64,
+    /// dsts skipped because the sender's filter says they have the message,
+    /// and how many of them the filter was wrong about.
+    bloom_skip_count: u32,
+    false_positive_skip_count: u32,
+    /// messages arrived at an offline node, and how often nodes crashed/joined from `start_ts`
+    /// until its last message arrived.
+    offline_drop_count: u32,
+    crash_count: u32,
+    join_count: u32,
+    /// sends lost on the link, not part of `send_*_count`.
+    lost_message_count: u32,
+    lost_hash_count: u32,
+    lost_ask_for_count: u32,
+    /// sends dropped by a partition, not part of `send_*_count`.
+    partition_drop_count: u32,
+    /// groups, summed over partitions, that had full message before their partition healed.
+    reached_group_count: u32,
+    /// ms from the last heal until every live node had full message,
+    /// `None` without partitions or if never.
+    heal_recovery_ts: Option<TimeStamp>,
+    /// per group reach of every partition.
+    partition_reach: Vec<PartitionReach>,
+    /// live honest nodes that recvd full message, and all live honest nodes.
+    honest_recv_node_size: u32,
+    honest_live_node_size: u32,
+    /// full messages and hashes recvd by droppers, ask fors ignored by nodes that had
+    /// full message, full messages sent with a lying bloomstatus and messages sent by spammers.
+    swallowed_count: u32,
+    unanswered_ask_for_count: u32,
+    lie_count: u32,
+    spam_count: u32,
+    /// sends pushed while an identical one was queued, delivered apart but once merged.
+    would_merge_count: u32,
+    /// grafts and prunes sent, and lost on the link.
+    send_control_count: u32,
+    lost_control_count: u32,
+    /// live nodes holding coded chunks, but too few to rebuild the message.
+    partial_node_size: u32,
+    /// mean ms from a node's first chunk until it rebuilt the message, `None` if none did.
+    mean_rebuild_delay: Option<f64>,
+    /// ms after `start_ts` until 50%/90%/99%/100% of nodes have recvd full message,
+    /// `None` if never.
+    coverage_50_ts: Option<TimeStamp>,
+    coverage_90_ts: Option<TimeStamp>,
+    coverage_99_ts: Option<TimeStamp>,
+    coverage_100_ts: Option<TimeStamp>,
+    /// delivery latency of full message over the nodes (apart from source) that recvd it.
+    mean_latency: Option<f64>,
+    p50_latency: Option<TimeStamp>,
+    p90_latency: Option<TimeStamp>,
+    p99_latency: Option<TimeStamp>,
 }
 
-pub fn summarize_data(
-    rp: &mut ResultPack,
-    node_status: &Vec<NodeStatus>,
-    message_queue: &MessageQueue,
-) {
+// getter
+impl ResultData {
+    pub fn message_seed(&self) -> u64 {
+        self.message_seed
+    }
+    pub fn source(&self) -> NodeId {
+        self.source
+    }
+    pub fn source_degree(&self) -> u32 {
+        self.source_degree
+    }
+    pub fn message_id(&self) -> MessageId {
+        self.message_id
+    }
+    pub fn start_ts(&self) -> TimeStamp {
+        self.start_ts
+    }
+    pub fn recv_node_size(&self) -> u32 {
+        self.recv_node_size
+    }
+    pub fn live_node_size(&self) -> u32 {
+        self.live_node_size
+    }
+    pub fn send_message_count(&self) -> u32 {
+        self.send_message_count
+    }
+    pub fn send_hash_count(&self) -> u32 {
+        self.send_hash_count
+    }
+    pub fn send_ask_for_count(&self) -> u32 {
+        self.send_ask_for_count
+    }
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+    pub fn payload_bytes(&self) -> u64 {
+        self.payload_bytes
+    }
+    pub fn hash_bytes(&self) -> u64 {
+        self.hash_bytes
+    }
+    pub fn bloomstatus_bytes(&self) -> u64 {
+        self.bloomstatus_bytes
+    }
+    pub fn header_bytes(&self) -> u64 {
+        self.header_bytes
+    }
+    pub fn mean_node_sent_bytes(&self) -> f64 {
+        self.mean_node_sent_bytes
+    }
+    pub fn p50_node_sent_bytes(&self) -> u64 {
+        self.p50_node_sent_bytes
+    }
+    pub fn p95_node_sent_bytes(&self) -> u64 {
+        self.p95_node_sent_bytes
+    }
+    pub fn max_node_sent_bytes(&self) -> u64 {
+        self.max_node_sent_bytes
+    }
+    pub fn mean_node_recv_bytes(&self) -> f64 {
+        self.mean_node_recv_bytes
+    }
+    pub fn p50_node_recv_bytes(&self) -> u64 {
+        self.p50_node_recv_bytes
+    }
+    pub fn p95_node_recv_bytes(&self) -> u64 {
+        self.p95_node_recv_bytes
+    }
+    pub fn max_node_recv_bytes(&self) -> u64 {
+        self.max_node_recv_bytes
+    }
+    pub fn bloom_skip_count(&self) -> u32 {
+        self.bloom_skip_count
+    }
+    pub fn false_positive_skip_count(&self) -> u32 {
+        self.false_positive_skip_count
+    }
+    pub fn offline_drop_count(&self) -> u32 {
+        self.offline_drop_count
+    }
+    pub fn crash_count(&self) -> u32 {
+        self.crash_count
+    }
+    pub fn join_count(&self) -> u32 {
+        self.join_count
+    }
+    pub fn lost_message_count(&self) -> u32 {
+        self.lost_message_count
+    }
+    pub fn lost_hash_count(&self) -> u32 {
+        self.lost_hash_count
+    }
+    pub fn lost_ask_for_count(&self) -> u32 {
+        self.lost_ask_for_count
+    }
+    pub fn partition_drop_count(&self) -> u32 {
+        self.partition_drop_count
+    }
+    pub fn reached_group_count(&self) -> u32 {
+        self.reached_group_count
+    }
+    pub fn heal_recovery_ts(&self) -> Option<TimeStamp> {
+        self.heal_recovery_ts
+    }
+    pub fn partition_reach(&self) -> &[PartitionReach] {
+        &self.partition_reach
+    }
+    pub fn honest_recv_node_size(&self) -> u32 {
+        self.honest_recv_node_size
+    }
+    pub fn honest_live_node_size(&self) -> u32 {
+        self.honest_live_node_size
+    }
+    pub fn swallowed_count(&self) -> u32 {
+        self.swallowed_count
+    }
+    pub fn unanswered_ask_for_count(&self) -> u32 {
+        self.unanswered_ask_for_count
+    }
+    pub fn lie_count(&self) -> u32 {
+        self.lie_count
+    }
+    pub fn spam_count(&self) -> u32 {
+        self.spam_count
+    }
+    pub fn would_merge_count(&self) -> u32 {
+        self.would_merge_count
+    }
+    pub fn send_control_count(&self) -> u32 {
+        self.send_control_count
+    }
+    pub fn lost_control_count(&self) -> u32 {
+        self.lost_control_count
+    }
+    pub fn partial_node_size(&self) -> u32 {
+        self.partial_node_size
+    }
+    pub fn mean_rebuild_delay(&self) -> Option<f64> {
+        self.mean_rebuild_delay
+    }
+    pub fn coverage_50_ts(&self) -> Option<TimeStamp> {
+        self.coverage_50_ts
+    }
+    pub fn coverage_90_ts(&self) -> Option<TimeStamp> {
+        self.coverage_90_ts
+    }
+    pub fn coverage_99_ts(&self) -> Option<TimeStamp> {
+        self.coverage_99_ts
+    }
+    pub fn coverage_100_ts(&self) -> Option<TimeStamp> {
+        self.coverage_100_ts
+    }
+    pub fn mean_latency(&self) -> Option<f64> {
+        self.mean_latency
+    }
+    pub fn p50_latency(&self) -> Option<TimeStamp> {
+        self.p50_latency
+    }
+    pub fn p90_latency(&self) -> Option<TimeStamp> {
+        self.p90_latency
+    }
+    pub fn p99_latency(&self) -> Option<TimeStamp> {
+        self.p99_latency
+    }
+}
+
+impl ResultData {
+    pub fn metric_names() -> &'static [&'static str] {
+        &[
+            "recv_node_size",
+            "live_node_size",
+            "send_message_count",
+            "send_hash_count",
+            "send_ask_for_count",
+            "total_bytes",
+            "payload_bytes",
+            "hash_bytes",
+            "bloomstatus_bytes",
+            "header_bytes",
+            "mean_node_sent_bytes",
+            "p50_node_sent_bytes",
+            "p95_node_sent_bytes",
+            "max_node_sent_bytes",
+            "mean_node_recv_bytes",
+            "p50_node_recv_bytes",
+            "p95_node_recv_bytes",
+            "max_node_recv_bytes",
+            "bloom_skip_count",
+            "false_positive_skip_count",
+            "offline_drop_count",
+            "crash_count",
+            "join_count",
+            "lost_message_count",
+            "lost_hash_count",
+            "lost_ask_for_count",
+            "partition_drop_count",
+            "reached_group_count",
+            "heal_recovery_ts",
+            "honest_recv_node_size",
+            "honest_live_node_size",
+            "swallowed_count",
+            "unanswered_ask_for_count",
+            "lie_count",
+            "spam_count",
+            "would_merge_count",
+            "send_control_count",
+            "lost_control_count",
+            "partial_node_size",
+            "mean_rebuild_delay",
+            "coverage_50_ts",
+            "coverage_90_ts",
+            "coverage_99_ts",
+            "coverage_100_ts",
+            "mean_latency",
+            "p50_latency",
+            "p90_latency",
+            "p99_latency",
+        ]
+    }
+
+    /// every metric of this message in [`ResultData::metric_names`] order,
+    /// `None` if the metric is not reached by this message.
+    pub fn metrics(&self) -> Vec<(&'static str, Option<f64>)> {
+        let ts = |ts: Option<TimeStamp>| ts.map(|ts| ts as f64);
+        let values = [
+            Some(self.recv_node_size as f64),
+            Some(self.live_node_size as f64),
+            Some(self.send_message_count as f64),
+            Some(self.send_hash_count as f64),
+            Some(self.send_ask_for_count as f64),
+            Some(self.total_bytes as f64),
+            Some(self.payload_bytes as f64),
+            Some(self.hash_bytes as f64),
+            Some(self.bloomstatus_bytes as f64),
+            Some(self.header_bytes as f64),
+            Some(self.mean_node_sent_bytes),
+            Some(self.p50_node_sent_bytes as f64),
+            Some(self.p95_node_sent_bytes as f64),
+            Some(self.max_node_sent_bytes as f64),
+            Some(self.mean_node_recv_bytes),
+            Some(self.p50_node_recv_bytes as f64),
+            Some(self.p95_node_recv_bytes as f64),
+            Some(self.max_node_recv_bytes as f64),
+            Some(self.bloom_skip_count as f64),
+            Some(self.false_positive_skip_count as f64),
+            Some(self.offline_drop_count as f64),
+            Some(self.crash_count as f64),
+            Some(self.join_count as f64),
+            Some(self.lost_message_count as f64),
+            Some(self.lost_hash_count as f64),
+            Some(self.lost_ask_for_count as f64),
+            Some(self.partition_drop_count as f64),
+            Some(self.reached_group_count as f64),
+            ts(self.heal_recovery_ts),
+            Some(self.honest_recv_node_size as f64),
+            Some(self.honest_live_node_size as f64),
+            Some(self.swallowed_count as f64),
+            Some(self.unanswered_ask_for_count as f64),
+            Some(self.lie_count as f64),
+            Some(self.spam_count as f64),
+            Some(self.would_merge_count as f64),
+            Some(self.send_control_count as f64),
+            Some(self.lost_control_count as f64),
+            Some(self.partial_node_size as f64),
+            self.mean_rebuild_delay,
+            ts(self.coverage_50_ts),
+            ts(self.coverage_90_ts),
+            ts(self.coverage_99_ts),
+            ts(self.coverage_100_ts),
+            self.mean_latency,
+            ts(self.p50_latency),
+            ts(self.p90_latency),
+            ts(self.p99_latency),
+        ];
+        ResultData::metric_names()
+            .iter()
+            .copied()
+            .zip(values)
+            .collect()
+    }
+}
+
+/// collect the result of the message of `message_id` that just spread on `net`.
+pub fn summarize_data(rp: &mut ResultPack, message_id: MessageId, net: &Network) {
+    let Broadcast {
+        message_seed,
+        source,
+        start_ts,
+    } = net.broadcast(message_id);
+    let node_status = net.node_status(message_id);
+    let message_queue = net.message_queue();
+    let traffic = net.traffic();
+    let failures = net.failures();
+    let packet_loss = net.packet_loss();
+    let partitions = net.partitions();
+    let behaviours = net.behaviours();
+    let live_nodes = failures.online();
+    // other messages in flight may outlast it
+    let end_ts = message_queue.last_deliver_ts(message_id);
     log::debug!("node_size: {}", node_status.len());
-    let recv_node_size = node_status
-        .iter()
-        .filter(|n| n.has_recv_full_message())
+    let live_node_size = live_nodes.iter().filter(|live| **live).count() as u32;
+    // only live nodes count, a node that recvd the message and then crashed does not
+    let live_status = || {
+        node_status
+            .iter()
+            .enumerate()
+            .filter(|(node_id, _)| live_nodes[*node_id])
+    };
+    let recv_node_size = live_status()
+        .filter(|(_, n)| n.has_recv_full_message())
         .count() as u32;
-    log::debug!("recv_nodes_size: {}", recv_node_size);
+    log::debug!(
+        "recv_nodes_size: {} of {} live nodes",
+        recv_node_size,
+        live_node_size
+    );
     log::debug!(
         "send message count: {}, send hash count: {}, send ask for count: {}",
-        message_queue.handled_messsage_count(),
-        message_queue.handled_hash_count(),
-        message_queue.handled_ask_for(),
+        message_queue.handled_messsage_count(message_id),
+        message_queue.handled_hash_count(message_id),
+        message_queue.handled_ask_for(message_id),
+    );
+    let bytes = traffic.total(message_id);
+    log::debug!(
+        "sent bytes: {} (payload {}, hash {}, bloomstatus {}, header {})",
+        bytes.total(),
+        bytes.payload,
+        bytes.hash,
+        bytes.bloomstatus,
+        bytes.header
+    );
+    let mut sent_bytes = traffic.sent_bytes(message_id);
+    sent_bytes.sort_unstable();
+    let mut recv_bytes = traffic.recv_bytes(message_id);
+    recv_bytes.sort_unstable();
+    let mean_bytes = |sorted: &[u64]| sorted.iter().sum::<u64>() as f64 / sorted.len() as f64;
+
+    // ts of each node first recvd full message, sorted
+    let mut recv_ts: Vec<TimeStamp> = live_status()
+        .filter_map(|(_, n)| n.recv_full_message_ts())
+        .collect();
+    recv_ts.sort_unstable();
+    let recvd_at = |fraction: f64| -> Option<TimeStamp> {
+        let need = (fraction * live_node_size as f64).ceil() as usize;
+        recv_ts.get(need.max(1) - 1).copied()
+    };
+    let coverage_ts = |fraction: f64| recvd_at(fraction).map(|ts| ts - start_ts);
+    let mut latency: Vec<TimeStamp> = live_status()
+        .filter(|(node_id, _)| *node_id != source)
+        .filter_map(|(_, n)| n.recv_full_message_ts())
+        .map(|ts| {
+            ts - node_status[source]
+                .recv_full_message_ts()
+                .unwrap_or(start_ts)
+        })
+        .collect();
+    latency.sort_unstable();
+    let mean_latency = if latency.is_empty() {
+        None
+    } else {
+        Some(latency.iter().map(|l| *l as f64).sum::<f64>() / latency.len() as f64)
+    };
+    log::debug!(
+        "coverage ts 50%: {:?} 100%: {:?}, mean latency: {:?}",
+        coverage_ts(0.5),
+        coverage_ts(1.0),
+        mean_latency
+    );
+
+    let recv_ts: Vec<Option<TimeStamp>> = node_status
+        .iter()
+        .map(|n| n.recv_full_message_ts())
+        .collect();
+    let partition_reach = partitions.reach(&recv_ts, live_nodes);
+    let heal_recovery_ts = partitions
+        .last_heal_ts()
+        .zip(recvd_at(1.0))
+        .map(|(heal, ts)| ts.saturating_sub(heal));
+    log::debug!(
+        "partition reach: {:?}, recovered {:?} ms after heal",
+        partition_reach,
+        heal_recovery_ts
     );
+
+    // chunks of coded broadcasts
+    let partial_node_size = live_status()
+        .filter(|(_, n)| n.recv_chunk_count() > 0 && !n.has_recv_full_message())
+        .count() as u32;
+    let rebuild_delay: Vec<TimeStamp> = live_status()
+        .filter(|(node_id, _)| *node_id != source)
+        .filter_map(|(_, n)| n.recv_full_message_ts()?.checked_sub(n.first_chunk_ts()?))
+        .collect();
+    let mean_rebuild_delay = if rebuild_delay.is_empty() {
+        None
+    } else {
+        Some(rebuild_delay.iter().map(|d| *d as f64).sum::<f64>() / rebuild_delay.len() as f64)
+    };
+
+    let honest_status =
+        || live_status().filter(|(node_id, _)| behaviours.of(*node_id) == Behaviour::Honest);
+    let honest_live_node_size = honest_status().count() as u32;
+    let honest_recv_node_size = honest_status()
+        .filter(|(_, n)| n.has_recv_full_message())
+        .count() as u32;
+    log::debug!(
+        "honest recv_nodes_size: {} of {} live honest nodes",
+        honest_recv_node_size,
+        honest_live_node_size
+    );
+
     rp.add_result(ResultData {
-        recv_node_size: recv_node_size,
-        send_message_count: message_queue.handled_messsage_count(),
-        send_hash_count: message_queue.handled_hash_count(),
-        send_ask_for_count: message_queue.handled_ask_for(),
+        message_seed,
+        source,
+        source_degree: net.topology().degree(source) as u32,
+        message_id,
+        start_ts,
+        recv_node_size,
+        live_node_size,
+        send_message_count: message_queue.handled_messsage_count(message_id),
+        send_hash_count: message_queue.handled_hash_count(message_id),
+        send_ask_for_count: message_queue.handled_ask_for(message_id),
+        total_bytes: bytes.total(),
+        payload_bytes: bytes.payload,
+        hash_bytes: bytes.hash,
+        bloomstatus_bytes: bytes.bloomstatus,
+        header_bytes: bytes.header,
+        mean_node_sent_bytes: mean_bytes(&sent_bytes),
+        p50_node_sent_bytes: percentile(&sent_bytes, 50.0).unwrap_or(0),
+        p95_node_sent_bytes: percentile(&sent_bytes, 95.0).unwrap_or(0),
+        max_node_sent_bytes: sent_bytes.last().copied().unwrap_or(0),
+        mean_node_recv_bytes: mean_bytes(&recv_bytes),
+        p50_node_recv_bytes: percentile(&recv_bytes, 50.0).unwrap_or(0),
+        p95_node_recv_bytes: percentile(&recv_bytes, 95.0).unwrap_or(0),
+        max_node_recv_bytes: recv_bytes.last().copied().unwrap_or(0),
+        bloom_skip_count: node_status.iter().map(|n| n.bloom_skip_count()).sum(),
+        false_positive_skip_count: node_status
+            .iter()
+            .map(|n| n.false_positive_skip_count())
+            .sum(),
+        offline_drop_count: failures.offline_drop_count(message_id),
+        crash_count: failures.crash_count(start_ts, end_ts),
+        join_count: failures.join_count(start_ts, end_ts),
+        lost_message_count: packet_loss.lost_message_count(message_id),
+        lost_hash_count: packet_loss.lost_hash_count(message_id),
+        lost_ask_for_count: packet_loss.lost_ask_for_count(message_id),
+        partition_drop_count: partitions.drop_count(message_id),
+        reached_group_count: partition_reach
+            .iter()
+            .map(|r| r.reached_group_count())
+            .sum(),
+        heal_recovery_ts,
+        partition_reach,
+        honest_recv_node_size,
+        honest_live_node_size,
+        swallowed_count: behaviours.swallowed_count(message_id),
+        unanswered_ask_for_count: behaviours.unanswered_ask_for_count(message_id),
+        lie_count: behaviours.lie_count(message_id),
+        spam_count: behaviours.spam_count(message_id),
+        would_merge_count: message_queue.would_merge_count(message_id),
+        send_control_count: message_queue.handled_control_count(message_id),
+        lost_control_count: packet_loss.lost_control_count(message_id),
+        partial_node_size,
+        mean_rebuild_delay,
+        coverage_50_ts: coverage_ts(0.5),
+        coverage_90_ts: coverage_ts(0.9),
+        coverage_99_ts: coverage_ts(0.99),
+        coverage_100_ts: coverage_ts(1.0),
+        mean_latency,
+        p50_latency: percentile(&latency, 50.0),
+        p90_latency: percentile(&latency, 90.0),
+        p99_latency: percentile(&latency, 99.0),
     });
 }
+
+fn format_mean_ci(summary: Option<Summary>) -> String {
+    match summary {
+        Some(summary) => summary.format_mean_ci(),
+        None => "-".to_string(),
+    }
+}
+
+fn format_optional(value: Option<f64>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+#[test]
+fn test_summarize_data() {
+    use crate::sim_rng::new_rng;
+
+    let params = ParamsPacket::builder(10, 3, 3, 2)
+        .partitions("split:2:0:1000".parse().unwrap())
+        .build()
+        .unwrap();
+    let mut net = Network::new(params.clone()).unwrap();
+    net.reset(1, new_rng(1), 2, 0);
+    net.send_broadcast(11, 0, 0);
+    net.send_broadcast(12, 3, 100);
+    // the first message stays in the source's group until it heals, then reaches 3 more
+    for (node, ts) in [(0, 0), (1, 100), (2, 200), (3, 300), (4, 400)]
+        .into_iter()
+        .chain([(5, 1500), (6, 1600), (7, 1700)])
+    {
+        net.node_status_mut(0, node).record_recv_message(ts);
+    }
+    // the second reaches everyone 100 ms after its source got it
+    net.node_status_mut(1, 3).record_recv_message(100);
+    for node in (0..10).filter(|node| *node != 3) {
+        net.node_status_mut(1, node).record_recv_message(200);
+    }
+    let mut rp = ResultPack::new(&params);
+    summarize_data(&mut rp, 0, &net);
+    summarize_data(&mut rp, 1, &net);
+
+    let [first, second] = rp.each_result_data() else {
+        panic!("expect 2 results");
+    };
+    assert_eq!((first.message_seed(), first.source()), (11, 0));
+    assert_eq!((first.recv_node_size(), first.live_node_size()), (8, 10));
+    assert_eq!(first.coverage_50_ts(), Some(400));
+    assert_eq!(first.coverage_90_ts(), None);
+    assert_eq!(first.mean_latency(), Some(5800.0 / 7.0));
+    assert_eq!(first.p50_latency(), Some(400));
+    assert_eq!(first.reached_group_count(), 1);
+    assert_eq!(first.heal_recovery_ts(), None);
+    assert_eq!(first.partition_reach()[0].recv_before_heal, vec![5, 0]);
+
+    assert_eq!((second.source(), second.start_ts()), (3, 100));
+    assert_eq!(second.coverage_100_ts(), Some(100));
+    assert_eq!(second.mean_latency(), Some(100.0));
+    assert_eq!(second.reached_group_count(), 2);
+    assert_eq!(second.heal_recovery_ts(), Some(0));
+    assert_eq!(rp.full_coverage_ratio(), 0.5);
+
+    let records = rp.partition_records();
+    assert_eq!(records.len(), 4);
+    assert_eq!(
+        records
+            .iter()
+            .map(|r| (r.message_index, r.source_node, r.group, r.recv_before_heal))
+            .collect::<Vec<_>>(),
+        vec![(0, 0, 0, 5), (0, 0, 1, 0), (1, 3, 0, 5), (1, 3, 1, 5)]
+    );
+    let sources: Vec<(NodeId, usize)> = rp
+        .by_source()
+        .iter()
+        .map(|(source, rp)| (*source, rp.each_result_data().len()))
+        .collect();
+    assert_eq!(sources, vec![(0, 1), (3, 1)]);
+    let records = rp.source_records();
+    assert_eq!(records[0].full_coverage_ratio, 0.0);
+    assert_eq!(records[1].avg_coverage_100_ts, Some(100.0));
+}
+
+#[test]
+fn test_churn_of_each_message() {
+    use crate::{latency::DelayKind, message::Message, sim_rng::new_rng};
+
+    let params = ParamsPacket::builder(100, 3, 3, 2)
+        .churn_rate(2.0)
+        .build()
+        .unwrap();
+    let mut net = Network::new(params.clone()).unwrap();
+    net.reset(1, new_rng(1), 2, 0);
+    // the first message spreads until about 500, the second from 1000 to about 3000
+    net.send_broadcast(11, 0, 0);
+    net.send_broadcast(12, 0, 1000);
+    let mut last = Message::build_send_full_message(0, 1, 1);
+    net.send(last.clone(), 400, DelayKind::Message);
+    last.message_id = 1;
+    net.send(last, 2900, DelayKind::Message);
+    while net.next_event().is_some() {}
+    let end_ts = |message_id| net.message_queue().last_deliver_ts(message_id);
+    let (end_0, end_1) = (end_ts(0), end_ts(1));
+    assert!(end_0 > 400 && end_1 > 2900);
+    net.advance_to(end_1);
+    let mut rp = ResultPack::new(&params);
+    summarize_data(&mut rp, 0, &net);
+    summarize_data(&mut rp, 1, &net);
+
+    let failures = net.failures();
+    let data = rp.each_result_data();
+    assert_eq!(data[0].crash_count(), failures.crash_count(0, end_0));
+    assert_eq!(data[1].crash_count(), failures.crash_count(1000, end_1));
+    assert_eq!(data[1].join_count(), failures.join_count(1000, end_1));
+    assert!(data[1].crash_count() > data[0].crash_count());
+}
+
+#[test]
+fn test_metric_summaries() {
+    use crate::rrs_simulator::RRSSimulator;
+
+    let params = ParamsPacket::builder(60, 3, 4, 20).seed(9).build().unwrap();
+    let rp = RRSSimulator::new(params).unwrap().run();
+    let summaries = rp.metric_summaries();
+    assert_eq!(summaries.len(), ResultData::metric_names().len());
+
+    let (name, recv) = &summaries[0];
+    assert_eq!(*name, "recv_node_size");
+    let recv = recv.as_ref().unwrap();
+    assert_eq!(recv.count, 20);
+    assert!((recv.mean - rp.avg_recv_node_size()).abs() < 1e-9);
+    assert!(recv.min <= recv.median && recv.median <= recv.max);
+    assert_eq!(Some(recv.clone()), rp.summary_of("recv_node_size"));
+
+    let full = rp
+        .each_result_data()
+        .iter()
+        .filter(|e| e.recv_node_size() == 60)
+        .count();
+    assert_eq!(rp.full_coverage_ratio(), full as f64 / 20.0);
+    assert_eq!(
+        rp.summary_of("coverage_100_ts").map_or(0, |s| s.count),
+        full
+    );
+}
+
+#[test]
+fn test_byte_accounting() {
+    use crate::{
+        message::{HASH_SIZE, HEADER_SIZE},
+        rrs_simulator::RRSSimulator,
+    };
+
+    let params = ParamsPacket::builder(80, 3, 3, 5)
+        .seed(4)
+        .payload_size(500)
+        .build()
+        .unwrap();
+    let rp = RRSSimulator::new(params).unwrap().run();
+    for e in rp.each_result_data() {
+        // the queue also counts the source's own message, which is never sent
+        let sent_message = e.send_message_count() as u64 - 1;
+        let sent = sent_message + (e.send_hash_count() + e.send_ask_for_count()) as u64;
+        assert_eq!(e.header_bytes(), sent * HEADER_SIZE as u64);
+        assert_eq!(e.payload_bytes(), sent_message * 500);
+        assert_eq!(
+            e.hash_bytes(),
+            (e.send_hash_count() + e.send_ask_for_count()) as u64 * HASH_SIZE as u64
+        );
+        assert!(e.bloomstatus_bytes() > 0);
+        assert_eq!(
+            e.total_bytes(),
+            e.header_bytes() + e.payload_bytes() + e.hash_bytes() + e.bloomstatus_bytes()
+        );
+        // nothing is lost, every byte sent is recvd, spread over the 80 nodes
+        assert_eq!(
+            (e.mean_node_sent_bytes() * 80.0).round() as u64,
+            e.total_bytes()
+        );
+        assert_eq!(e.mean_node_recv_bytes(), e.mean_node_sent_bytes());
+        assert!(e.p50_node_sent_bytes() <= e.p95_node_sent_bytes());
+        assert!(e.p95_node_sent_bytes() <= e.max_node_sent_bytes());
+        assert!(e.p95_node_recv_bytes() <= e.max_node_recv_bytes());
+        assert!(e.max_node_sent_bytes() <= e.total_bytes());
+    }
+}
@@ -0,0 +1,363 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+
+use crate::{
+    behaviour::Behaviour,
+    message::{Message, MessageId, MessageStatus, NodeId},
+    message_queue::{TimeStamp, Timer},
+    network::Network,
+    protocol::{send, Protocol},
+    sim_rng::{stream_rng, PROTOCOL_STREAM},
+};
+
+/// Epidemic broadcast tree. Each node pushes the full message to its eager peers and only an
+/// `IHAVE` hash to its lazy ones. A duplicate prunes the link it came on into a lazy one, so the
+/// eager links shrink into a spanning tree over successive messages. A node that hears `IHAVE`
+/// but misses the message `timeout` ms later grafts the announcer back into the tree.
+#[derive(Debug, Clone)]
+pub struct Plumtree {
+    timeout: u32,
+    /// peers each node pushes full message to. Starts as `t` random neighbours, both ways.
+    eager: Vec<BTreeSet<NodeId>>,
+    /// peers each node announces hashes to.
+    lazy: Vec<BTreeSet<NodeId>>,
+    /// announcers of messages a node heard of but has not got.
+    missing: HashMap<(MessageId, NodeId), Missing>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Missing {
+    /// lazy peers that sent `IHAVE`, graft them in this order.
+    announcers: VecDeque<NodeId>,
+    timer_set: bool,
+}
+
+impl Plumtree {
+    pub fn new(timeout: u32) -> Plumtree {
+        Plumtree {
+            timeout,
+            eager: Vec::new(),
+            lazy: Vec::new(),
+            missing: HashMap::new(),
+        }
+    }
+
+    pub fn eager(&self, node: NodeId) -> &BTreeSet<NodeId> {
+        &self.eager[node]
+    }
+    pub fn lazy(&self, node: NodeId) -> &BTreeSet<NodeId> {
+        &self.lazy[node]
+    }
+
+    fn make_eager(&mut self, node: NodeId, peer: NodeId) {
+        self.lazy[node].remove(&peer);
+        self.eager[node].insert(peer);
+    }
+
+    fn make_lazy(&mut self, node: NodeId, peer: NodeId) {
+        self.eager[node].remove(&peer);
+        self.lazy[node].insert(peer);
+    }
+
+    fn on_gossip(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, from, message_id) = (message.to, message.from, message.message_id);
+        let behaviour = net.behaviours().of(node);
+        let status = net.node_status_mut(message_id, node);
+        status.record_recv_message(ts);
+        let first = status.recv_message_count() == 1;
+        if behaviour == Behaviour::Dropper {
+            net.behaviours_mut().record_swallowed(message_id);
+            return;
+        }
+        if first {
+            self.missing.remove(&(message_id, node));
+            // the source gets it from itself
+            if from != node {
+                self.make_eager(node, from);
+            }
+        } else {
+            self.make_lazy(node, from);
+            send(net, node, from, message_id, MessageStatus::Prune, 0, ts);
+        }
+        // a spammer pushes copies on too, within `max_hop_num` hops and its spam budget
+        let spam = behaviour == Behaviour::Spammer
+            && message.hop_num < net.params().max_hop_num()
+            && net.node_status_mut(message_id, node).spend_spam_budget();
+        if !first && !spam {
+            return;
+        }
+        let hop_num = message.hop_num + 1;
+        for dst in self.eager[node].clone() {
+            if dst != from {
+                send(
+                    net,
+                    node,
+                    dst,
+                    message_id,
+                    MessageStatus::FullMessage,
+                    hop_num,
+                    ts,
+                );
+            }
+        }
+        for dst in self.lazy[node].clone() {
+            if dst != from {
+                send(
+                    net,
+                    node,
+                    dst,
+                    message_id,
+                    MessageStatus::OnlyHash,
+                    hop_num,
+                    ts,
+                );
+            }
+        }
+    }
+
+    fn on_ihave(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, message_id) = (message.to, message.message_id);
+        let status = net.node_status_mut(message_id, node);
+        status.record_recv_hash();
+        if net.behaviours().of(node) == Behaviour::Dropper {
+            net.behaviours_mut().record_swallowed(message_id);
+            return;
+        }
+        if net.node_status(message_id)[node].has_recv_full_message() {
+            return;
+        }
+        let missing = self.missing.entry((message_id, node)).or_default();
+        missing.announcers.push_back(message.from);
+        if !missing.timer_set {
+            missing.timer_set = true;
+            let timer = Timer {
+                node,
+                message_id,
+                tag: 0,
+            };
+            net.set_timer(timer, ts + self.timeout);
+        }
+    }
+
+    fn on_graft(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let (node, message_id) = (message.to, message.message_id);
+        let behaviour = net.behaviours().of(node);
+        if behaviour == Behaviour::Dropper {
+            net.behaviours_mut().record_swallowed(message_id);
+            return;
+        }
+        self.make_eager(node, message.from);
+        if !net.node_status(message_id)[node].has_recv_full_message() {
+            return;
+        }
+        if !behaviour.replies_ask_for() {
+            log::debug!("  {} ignores graft", node);
+            net.behaviours_mut().record_unanswered_ask_for(message_id);
+            return;
+        }
+        send(
+            net,
+            node,
+            message.from,
+            message_id,
+            MessageStatus::FullMessage,
+            0,
+            ts,
+        );
+    }
+}
+
+impl Protocol for Plumtree {
+    fn start(&mut self, net: &Network) {
+        let node_size = net.node_size();
+        let t = net.params().t() as usize;
+        let mut rng = stream_rng(net.params().seed(), PROTOCOL_STREAM);
+        self.eager = vec![BTreeSet::new(); node_size];
+        self.lazy = vec![BTreeSet::new(); node_size];
+        for node in 0..node_size {
+            for peer in net.topology().random_neighbours(node, t, &mut rng) {
+                self.eager[node].insert(peer);
+                self.eager[peer].insert(node);
+            }
+        }
+        self.missing.clear();
+    }
+
+    fn reset(&mut self, _net: &Network) {
+        self.missing.clear();
+    }
+
+    fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        log::debug!("{} handle message {:?} at ts {}", message.to, message, ts);
+        match message.status {
+            MessageStatus::FullMessage => self.on_gossip(net, message, ts),
+            MessageStatus::OnlyHash => self.on_ihave(net, message, ts),
+            MessageStatus::Graft => self.on_graft(net, message, ts),
+            MessageStatus::Prune => self.make_lazy(message.to, message.from),
+            // plumtree never asks
+            MessageStatus::AskForMessage => {}
+        }
+    }
+
+    /// still missing the message: graft the first peer that announced it, and wait for the next.
+    fn on_timer(&mut self, net: &mut Network, timer: Timer, ts: TimeStamp) {
+        let key = (timer.message_id, timer.node);
+        if net.node_status(timer.message_id)[timer.node].has_recv_full_message() {
+            self.missing.remove(&key);
+            return;
+        }
+        let missing = match self.missing.get_mut(&key) {
+            Some(missing) => missing,
+            None => return,
+        };
+        let announcer = match missing.announcers.pop_front() {
+            Some(announcer) => announcer,
+            None => {
+                missing.timer_set = false;
+                return;
+            }
+        };
+        if missing.announcers.is_empty() {
+            missing.timer_set = false;
+        } else {
+            net.set_timer(timer.clone(), ts + self.timeout);
+        }
+        self.make_eager(timer.node, announcer);
+        send(
+            net,
+            timer.node,
+            announcer,
+            timer.message_id,
+            MessageStatus::Graft,
+            0,
+            ts,
+        );
+    }
+}
+
+#[cfg(test)]
+fn started(params: crate::params_packet::ParamsPacket) -> (Plumtree, Network) {
+    let net = Network::for_message(params, 0);
+    let mut plumtree = Plumtree::new(200);
+    plumtree.start(&net);
+    (plumtree, net)
+}
+
+#[test]
+fn test_plumtree_start() {
+    use crate::params_packet::ParamsPacket;
+
+    let (plumtree, _) = started(ParamsPacket::builder(100, 4, 4, 1).seed(1).build().unwrap());
+    for node in 0..100 {
+        assert!(plumtree.eager(node).len() >= 4);
+        assert!(!plumtree.eager(node).contains(&node));
+        assert!(plumtree.lazy(node).is_empty());
+        assert!(plumtree
+            .eager(node)
+            .iter()
+            .all(|peer| plumtree.eager(*peer).contains(&node)));
+    }
+}
+
+#[test]
+fn test_plumtree_gossip() {
+    use crate::params_packet::ParamsPacket;
+
+    let (mut plumtree, mut net) =
+        started(ParamsPacket::builder(100, 4, 4, 1).seed(1).build().unwrap());
+    let full = |from, to, hop_num| Message::build_send_full_message(from, to, hop_num);
+
+    // pushed to every eager peer
+    plumtree.on_receive(&mut net, full(0, 0, 0), 0);
+    let pushed = net.drain_messages();
+    assert_eq!(
+        &pushed.iter().map(|m| m.to).collect::<BTreeSet<_>>(),
+        plumtree.eager(0)
+    );
+    assert!(pushed
+        .iter()
+        .all(|m| m.status == MessageStatus::FullMessage && m.hop_num == 1));
+
+    // the first copy makes its sender eager, a duplicate prunes it to lazy both ways
+    let (node, other) = (50, 60);
+    plumtree.on_receive(&mut net, full(other, node, 1), 100);
+    assert!(plumtree.eager(node).contains(&other));
+    net.drain_messages();
+    plumtree.on_receive(&mut net, full(other, node, 2), 200);
+    let prune = net.drain_messages();
+    assert_eq!(
+        prune,
+        vec![Message::build_send_control_message(
+            node,
+            other,
+            MessageStatus::Prune
+        )]
+    );
+    assert!(plumtree.lazy(node).contains(&other));
+    plumtree.on_receive(&mut net, prune[0].clone(), 300);
+    assert!(plumtree.lazy(other).contains(&node));
+    assert!(!plumtree.eager(other).contains(&node));
+
+    // lazy peers get the hash only
+    plumtree.on_receive(&mut net, full(0, other, 1), 400);
+    let sent = net.drain_messages();
+    assert!(sent
+        .iter()
+        .any(|m| m.to == node && m.status == MessageStatus::OnlyHash));
+    assert!(sent
+        .iter()
+        .filter(|m| m.status == MessageStatus::FullMessage)
+        .all(|m| plumtree.eager(other).contains(&m.to)));
+}
+
+#[test]
+fn test_plumtree_graft() {
+    use crate::{message_queue::Event, params_packet::ParamsPacket};
+
+    let (mut plumtree, mut net) =
+        started(ParamsPacket::builder(100, 4, 4, 1).seed(1).build().unwrap());
+    let node = 50;
+    let timer = Timer {
+        node,
+        message_id: 0,
+        tag: 0,
+    };
+
+    // one timer for any number of announcers
+    plumtree.on_receive(&mut net, Message::build_send_hash_message(1, node, 1), 100);
+    plumtree.on_receive(&mut net, Message::build_send_hash_message(2, node, 1), 150);
+    assert_eq!(net.drain_events(), vec![(Event::Timer(timer.clone()), 300)]);
+
+    // graft the announcers in order, re-arming while more remain
+    let graft = |to| Message::build_send_control_message(node, to, MessageStatus::Graft);
+    plumtree.on_timer(&mut net, timer.clone(), 300);
+    let events = net.drain_events();
+    assert_eq!(events.len(), 2);
+    assert!(events.contains(&(Event::Timer(timer.clone()), 500)));
+    assert!(events
+        .iter()
+        .any(|(event, _)| *event == Event::Deliver(graft(1))));
+    assert!(plumtree.eager(node).contains(&1));
+    plumtree.on_timer(&mut net, timer.clone(), 500);
+    assert_eq!(net.drain_messages(), vec![graft(2)]);
+    plumtree.on_timer(&mut net, timer.clone(), 700);
+    assert!(net.drain_events().is_empty());
+
+    // a graft is answered with full message by a node that has it
+    plumtree.on_receive(&mut net, Message::build_send_full_message(0, 1, 1), 800);
+    net.drain_messages();
+    plumtree.on_receive(&mut net, graft(1), 900);
+    assert!(plumtree.eager(1).contains(&node));
+    assert_eq!(
+        net.drain_messages(),
+        vec![Message::build_send_full_message(1, node, 0)]
+    );
+    plumtree.on_receive(&mut net, graft(3), 900);
+    assert!(net.drain_messages().is_empty());
+
+    // no graft once it got the message
+    plumtree.on_receive(&mut net, Message::build_send_hash_message(4, 5, 1), 1000);
+    plumtree.on_receive(&mut net, Message::build_send_full_message(1, 5, 1), 1100);
+    net.drain_events();
+    plumtree.on_timer(&mut net, Timer { node: 5, ..timer }, 1200);
+    assert!(net.drain_events().is_empty());
+}
@@ -0,0 +1,270 @@
+use std::{fmt, str::FromStr};
+
+use serde::Deserialize;
+
+use crate::{
+    baseline::{Flood, PushGossip},
+    erasure::ErasureCoded,
+    gossipsub::Gossipsub,
+    latency::DelayKind,
+    message::{Message, MessageId, MessageStatus, NodeId},
+    message_queue::{TimeStamp, Timer},
+    network::Network,
+    plumtree::Plumtree,
+    rrs::Rrs,
+};
+
+/// ms a plumtree node waits for the full message after an `IHAVE` before it grafts.
+pub const DEFAULT_PLUMTREE_TIMEOUT: u32 = 200;
+/// mesh degree, its bounds and the heartbeat interval in ms of gossipsub, as libp2p has them.
+pub const DEFAULT_GOSSIPSUB_D: (u32, u32, u32) = (6, 5, 12);
+pub const DEFAULT_GOSSIPSUB_HEARTBEAT: u32 = 1000;
+
+/// How nodes spread a broadcast. The simulator pops events from the network in ts order and
+/// hands them to the protocol, which reacts by sending messages and setting timers on it.
+pub trait Protocol: fmt::Debug {
+    /// a run starts on `net`, before its first message. State kept across messages starts here.
+    fn start(&mut self, _net: &Network) {}
+
+    /// `net` was reset for the next message (or all concurrent ones), drop per-message state.
+    fn reset(&mut self, _net: &Network) {}
+
+    /// `message` arrived at live node `message.to` at `ts`. A broadcast starts with the full
+    /// message arriving at its source from itself at hop 0.
+    fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp);
+
+    /// `timer` set by `timer.node` fired at `ts`.
+    fn on_timer(&mut self, _net: &mut Network, _timer: Timer, _ts: TimeStamp) {}
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(try_from = "String")]
+pub enum ProtocolKind {
+    /// push full message for `k` rounds then hashes, pull with ask for on a hash.
+    #[default]
+    Rrs,
+    /// send full message to every neighbour once.
+    Flood,
+    /// push full message to `t` random neighbours once, for at most `ttl` hops.
+    Push { ttl: u32 },
+    /// push full message along a spanning tree of eager peers and `IHAVE` hashes to lazy
+    /// ones, graft a lazy peer that announced it if the message is `timeout` ms late.
+    Plumtree { timeout: u32 },
+    /// push full message to a mesh of `d` peers (kept in `d_lo..=d_hi`), announce it to `d`
+    /// others at each `heartbeat` for them to pull, optionally dropping low scored peers.
+    Gossipsub {
+        d: u32,
+        d_lo: u32,
+        d_hi: u32,
+        heartbeat: u32,
+        scoring: bool,
+    },
+    /// push `n` coded chunks of the payload for `k` hops, any `m` of them rebuild it.
+    Erasure { m: u32, n: u32 },
+}
+
+impl ProtocolKind {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ProtocolKind::Push { ttl: 0 } => Err(format!("{}: ttl must be at least 1", self)),
+            ProtocolKind::Plumtree { timeout: 0 } => {
+                Err(format!("{}: timeout must be at least 1", self))
+            }
+            ProtocolKind::Gossipsub { d, d_lo, d_hi, .. }
+                if !(1 <= *d_lo && d_lo <= d && d <= d_hi) =>
+            {
+                Err(format!("{}: expect 1 <= d_lo <= d <= d_hi", self))
+            }
+            ProtocolKind::Gossipsub { heartbeat: 0, .. } => {
+                Err(format!("{}: heartbeat must be at least 1", self))
+            }
+            ProtocolKind::Erasure { m, n } if !(1 <= *m && m <= n) => {
+                Err(format!("{}: expect 1 <= m <= n", self))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Protocol> {
+        match *self {
+            ProtocolKind::Rrs => Box::new(Rrs),
+            ProtocolKind::Flood => Box::new(Flood),
+            ProtocolKind::Push { ttl } => Box::new(PushGossip { ttl }),
+            ProtocolKind::Plumtree { timeout } => Box::new(Plumtree::new(timeout)),
+            ProtocolKind::Gossipsub {
+                d,
+                d_lo,
+                d_hi,
+                heartbeat,
+                scoring,
+            } => Box::new(Gossipsub::new(d, d_lo, d_hi, heartbeat, scoring)),
+            ProtocolKind::Erasure { m, n } => Box::new(ErasureCoded { m, n }),
+        }
+    }
+}
+
+/// `rrs`, `flood`, `push:<ttl>`, `plumtree[:<timeout>]`,
+/// `gossipsub[:<d>:<d_lo>:<d_hi>[:<heartbeat>]][:score]` or `erasure:<m>:<n>`.
+impl FromStr for ProtocolKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let parse = |v: &str| v.parse::<u32>().map_err(|e| format!("{} in `{}`", e, s));
+        match parts.as_slice() {
+            ["rrs"] => Ok(ProtocolKind::Rrs),
+            ["flood"] => Ok(ProtocolKind::Flood),
+            ["push", ttl] => Ok(ProtocolKind::Push { ttl: parse(ttl)? }),
+            ["plumtree"] => Ok(ProtocolKind::Plumtree {
+                timeout: DEFAULT_PLUMTREE_TIMEOUT,
+            }),
+            ["plumtree", timeout] => Ok(ProtocolKind::Plumtree {
+                timeout: parse(timeout)?,
+            }),
+            ["erasure", m, n] => Ok(ProtocolKind::Erasure {
+                m: parse(m)?,
+                n: parse(n)?,
+            }),
+            ["gossipsub", rest @ ..] => {
+                let (rest, scoring) = match rest.split_last() {
+                    Some((&"score", rest)) => (rest, true),
+                    _ => (rest, false),
+                };
+                let (d, d_lo, d_hi) = match rest {
+                    [] => DEFAULT_GOSSIPSUB_D,
+                    [d, d_lo, d_hi, ..] => (parse(d)?, parse(d_lo)?, parse(d_hi)?),
+                    _ => return Err(format!("expect d, d_lo and d_hi in `{}`", s)),
+                };
+                let heartbeat = match rest {
+                    [] | [_, _, _] => DEFAULT_GOSSIPSUB_HEARTBEAT,
+                    [_, _, _, heartbeat] => parse(heartbeat)?,
+                    _ => return Err(format!("too many values in `{}`", s)),
+                };
+                Ok(ProtocolKind::Gossipsub {
+                    d,
+                    d_lo,
+                    d_hi,
+                    heartbeat,
+                    scoring,
+                })
+            }
+            _ => Err(format!(
+                "unknown protocol `{}`, expect rrs, flood, push:<ttl>, plumtree[:<timeout>], gossipsub[:<d>:<d_lo>:<d_hi>[:<heartbeat>]][:score] or erasure:<m>:<n>",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for ProtocolKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ProtocolKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolKind::Rrs => write!(f, "rrs"),
+            ProtocolKind::Flood => write!(f, "flood"),
+            ProtocolKind::Push { ttl } => write!(f, "push:{}", ttl),
+            ProtocolKind::Plumtree { timeout } => write!(f, "plumtree:{}", timeout),
+            ProtocolKind::Gossipsub {
+                d,
+                d_lo,
+                d_hi,
+                heartbeat,
+                scoring,
+            } => {
+                write!(f, "gossipsub:{}:{}:{}:{}", d, d_lo, d_hi, heartbeat)?;
+                if *scoring {
+                    write!(f, ":score")?;
+                }
+                Ok(())
+            }
+            ProtocolKind::Erasure { m, n } => write!(f, "erasure:{}:{}", m, n),
+        }
+    }
+}
+
+/// send a message of `status` about `message_id` from `from` to `to`.
+pub(crate) fn send(
+    net: &mut Network,
+    from: NodeId,
+    to: NodeId,
+    message_id: MessageId,
+    status: MessageStatus,
+    hop_num: u32,
+    ts: TimeStamp,
+) {
+    let kind = match status {
+        MessageStatus::FullMessage => DelayKind::Message,
+        MessageStatus::OnlyHash | MessageStatus::Prune => DelayKind::Hash,
+        MessageStatus::AskForMessage | MessageStatus::Graft => DelayKind::AskFor,
+    };
+    let mut message = match status {
+        MessageStatus::FullMessage => Message::build_send_full_message(from, to, hop_num),
+        MessageStatus::OnlyHash => Message::build_send_hash_message(from, to, hop_num),
+        MessageStatus::AskForMessage => Message::build_send_query_message(from, to),
+        MessageStatus::Graft | MessageStatus::Prune => {
+            Message::build_send_control_message(from, to, status)
+        }
+    };
+    message.message_id = message_id;
+    let (next_ts, res) = net.send(message.clone(), ts, kind);
+    log::debug!(
+        "  -> send {:?} to {} ts: {}  res:{}",
+        message,
+        to,
+        next_ts,
+        res
+    );
+}
+
+#[test]
+fn test_parse_protocol_kind() {
+    assert_eq!("rrs".parse(), Ok(ProtocolKind::Rrs));
+    assert_eq!(ProtocolKind::default().to_string(), "rrs");
+    assert_eq!("push:6".parse(), Ok(ProtocolKind::Push { ttl: 6 }));
+    assert_eq!(
+        "plumtree".parse(),
+        Ok(ProtocolKind::Plumtree {
+            timeout: DEFAULT_PLUMTREE_TIMEOUT
+        })
+    );
+    assert_eq!(
+        "gossipsub:score".parse(),
+        Ok(ProtocolKind::Gossipsub {
+            d: 6,
+            d_lo: 5,
+            d_hi: 12,
+            heartbeat: DEFAULT_GOSSIPSUB_HEARTBEAT,
+            scoring: true
+        })
+    );
+    for s in [
+        "rrs",
+        "flood",
+        "push:6",
+        "plumtree:50",
+        "gossipsub:8:6:12:700",
+        "gossipsub:6:5:12:1000:score",
+        "erasure:4:8",
+    ] {
+        assert_eq!(s.parse::<ProtocolKind>().unwrap().to_string(), s);
+    }
+    assert!("push".parse::<ProtocolKind>().is_err());
+    assert!("gossip".parse::<ProtocolKind>().is_err());
+    assert!(ProtocolKind::Push { ttl: 0 }.validate().is_err());
+    assert!(ProtocolKind::Plumtree { timeout: 0 }.validate().is_err());
+    assert!("gossipsub:6:5".parse::<ProtocolKind>().is_err());
+    assert!("erasure:4".parse::<ProtocolKind>().is_err());
+    assert!(ProtocolKind::Erasure { m: 5, n: 4 }.validate().is_err());
+    assert!("gossipsub:4:5:12"
+        .parse::<ProtocolKind>()
+        .unwrap()
+        .validate()
+        .is_err());
+}
@@ -0,0 +1,397 @@
+use crate::{
+    behaviour::Behaviour,
+    bloom::NodeFilter,
+    latency::DelayKind,
+    message::{Message, MessageId, MessageStatus, NodeId},
+    message_queue::TimeStamp,
+    network::Network,
+    node_status::UpdateBloomFilter,
+    protocol::Protocol,
+};
+
+/// Push the full message to `t` random neighbours for the first `k` hops, then only its hash
+/// up to `max_hop_num`. A node that got the hash but not the message asks a random neighbour
+/// for it, again after `send_ask_interval`. Each node's known set rides along as `bloomstatus`
+/// so that nodes which have it are skipped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rrs;
+
+impl Protocol for Rrs {
+    fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+        let send_node_id = message.to;
+        let message_id = message.message_id;
+        let behaviour = net.behaviours().of(send_node_id);
+        let k = net.params().k();
+        let each_handle_count = net.params().each_handle_count();
+        log::debug!("{} handle message {:?} at ts {}", send_node_id, message, ts);
+        let next_hop_num = message.hop_num + 1;
+        if next_hop_num > net.params().max_hop_num() {
+            return;
+        }
+        let mut send_node_status = net.node_status_mut(message_id, send_node_id);
+        match message.status {
+            MessageStatus::FullMessage => {
+                send_node_status.record_recv_message(ts);
+
+                if behaviour == Behaviour::Dropper {
+                    net.behaviours_mut().record_swallowed(message_id);
+                    return;
+                }
+                let stop = if behaviour == Behaviour::Spammer {
+                    !send_node_status.spend_spam_budget()
+                } else {
+                    send_node_status.stop_handle_message(each_handle_count)
+                };
+                if stop {
+                    return;
+                }
+
+                if next_hop_num <= k || behaviour == Behaviour::Spammer {
+                    let mut send_message =
+                        Message::build_send_full_message(send_node_id, send_node_id, next_hop_num); // set `to` later when push it in message queue
+                    send_message.message_id = message_id;
+
+                    // update send nodes' bloom status
+                    send_node_status.update_bloom_filter(&message.from);
+                    send_node_status.update_bloom_filter(&message.bloomstatus);
+
+                    // get dst list, spammers send to anyone
+                    let known = net.node_status(message_id)[send_node_id]
+                        .bloom_filter()
+                        .clone();
+                    let dst_list = if behaviour == Behaviour::Spammer {
+                        self.get_send_dst_list(net, send_node_id, message_id, None)
+                    } else {
+                        self.get_send_dst_list(net, send_node_id, message_id, Some(&known))
+                    };
+
+                    let mut send_node_status = net.node_status_mut(message_id, send_node_id);
+                    // update send nodes' bloom status of ready to send nodes.
+                    send_node_status.update_bloom_filter(&dst_list);
+                    if dst_list.is_empty() {
+                        return;
+                    }
+
+                    // update message's bloomfilter, a liar claims every node has it
+                    send_message.bloomstatus = if behaviour == Behaviour::BloomLiar {
+                        let mut lie = net.params().bloom_filter().new_filter();
+                        (0..net.node_size()).for_each(|node| lie.insert(node));
+                        lie
+                    } else {
+                        net.node_status(message_id)[send_node_id]
+                            .bloom_filter()
+                            .clone()
+                    };
+
+                    // push message into queue
+                    dst_list.iter().for_each(|dst| {
+                        send_message.to = *dst;
+                        if behaviour == Behaviour::BloomLiar {
+                            net.behaviours_mut().record_lie(message_id);
+                        }
+                        let (next_ts, res) = net.send(send_message.clone(), ts, DelayKind::Message);
+
+                        log::debug!(
+                            "  -> send msg {:?} to {} ts: {}  res:{} queue_len:{}",
+                            send_message,
+                            *dst,
+                            next_ts,
+                            res,
+                            net.message_queue().len()
+                        );
+                    })
+                } else if next_hop_num < net.params().max_hop_num() {
+                    // after k round, send msg hash
+                    let mut send_message =
+                        Message::build_send_hash_message(send_node_id, send_node_id, next_hop_num);
+                    send_message.message_id = message_id;
+
+                    // get dst list
+                    let dst_list = self.get_send_dst_list(net, send_node_id, message_id, None);
+
+                    // push message into queue
+                    dst_list.iter().for_each(|dst| {
+                        send_message.to = *dst;
+                        let (next_ts, res) = net.send(send_message.clone(), ts, DelayKind::Hash);
+                        log::debug!(
+                            "  -> send hash {:?} to {} ts: {}  res:{} queue_len:{}",
+                            send_message,
+                            *dst,
+                            next_ts,
+                            res,
+                            net.message_queue().len()
+                        );
+                    })
+                }
+            }
+            MessageStatus::OnlyHash => {
+                send_node_status.record_recv_hash();
+
+                if behaviour == Behaviour::Dropper {
+                    net.behaviours_mut().record_swallowed(message_id);
+                    return;
+                }
+                let stop = if behaviour == Behaviour::Spammer {
+                    !send_node_status.spend_spam_budget()
+                } else {
+                    send_node_status.stop_handle_message(each_handle_count)
+                };
+                if stop {
+                    return;
+                }
+
+                let mut send_message =
+                    Message::build_send_hash_message(send_node_id, send_node_id, next_hop_num);
+                send_message.message_id = message_id;
+
+                // get dst list
+                let dst_list = self.get_send_dst_list(net, send_node_id, message_id, None);
+
+                // push message into queue
+                dst_list.iter().for_each(|dst| {
+                    send_message.to = *dst;
+                    let (next_ts, res) = net.send(send_message.clone(), ts, DelayKind::Hash);
+                    log::debug!(
+                        "  -> send hash {:?} to {} ts: {}  res:{} queue_len:{}",
+                        send_message,
+                        *dst,
+                        next_ts,
+                        res,
+                        net.message_queue().len()
+                    );
+                });
+
+                // ask for if not recvd
+                let asker = &net.node_status(message_id)[send_node_id];
+                if !asker.has_recv_full_message()
+                    && (asker.send_ask_for_ts() == 0
+                        || ((asker.send_ask_for_ts() + net.params().send_ask_interval()) < ts))
+                {
+                    let ask_dst = match net.random_neighbours(send_node_id, 1).first() {
+                        Some(ask_dst) => *ask_dst,
+                        None => return,
+                    };
+                    let mut send_ask_message =
+                        Message::build_send_query_message(send_node_id, ask_dst);
+                    send_ask_message.message_id = message_id;
+                    let mut send_node_status = net.node_status_mut(message_id, send_node_id);
+                    send_node_status.record_send_ask_for(ts);
+                    let (next_ts, res) = net.send(send_ask_message.clone(), ts, DelayKind::AskFor);
+                    log::debug!(
+                        "  -> send ask for {:?} to {} ts: {}  res:{} queue_len:{}",
+                        send_ask_message,
+                        message.from,
+                        next_ts,
+                        res,
+                        net.message_queue().len()
+                    );
+                }
+            }
+            MessageStatus::AskForMessage => {
+                if net.node_status(message_id)[send_node_id].has_recv_full_message() {
+                    if !behaviour.replies_ask_for() {
+                        log::debug!("  {} ignores ask for", send_node_id);
+                        net.behaviours_mut().record_unanswered_ask_for(message_id);
+                        return;
+                    }
+                    let mut send_message =
+                        Message::build_send_full_message(send_node_id, message.from, 0);
+                    send_message.message_id = message_id;
+                    send_message.bloomstatus = net.params().bloom_filter().new_filter();
+                    let (next_ts, res) = net.send(send_message.clone(), ts, DelayKind::ReplyAsk);
+                    log::debug!(
+                        "  -> send reply ask {:?} to {} ts: {}  res:{} queue_len:{}",
+                        send_message,
+                        message.from,
+                        next_ts,
+                        res,
+                        net.message_queue().len()
+                    );
+                }
+            }
+            // rrs keeps no peers to steer
+            MessageStatus::Graft | MessageStatus::Prune => {}
+        }
+    }
+}
+
+impl Rrs {
+    fn get_send_dst_list(
+        &self,
+        net: &mut Network,
+        src_node_id: NodeId,
+        message_id: MessageId,
+        bloomstatus: Option<&NodeFilter>,
+    ) -> Vec<NodeId> {
+        let max_num = net.params().t();
+        let rand_neighbour = net.random_neighbours(src_node_id, max_num as usize);
+        let bloomstatus = match bloomstatus {
+            Some(bloomstatus) => bloomstatus,
+            None => return rand_neighbour,
+        };
+        let src_node_status = net.node_status_mut(message_id, src_node_id);
+        rand_neighbour
+            .into_iter()
+            .filter(|d| {
+                if !bloomstatus.contains(*d) {
+                    true
+                } else {
+                    let false_positive = bloomstatus.is_false_positive(*d);
+                    log::debug!(
+                        "  filtered, wont send {} (false positive: {})",
+                        d,
+                        false_positive
+                    );
+                    src_node_status.record_bloom_skip(false_positive);
+                    false
+                }
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_rrs_on_receive() {
+    use crate::params_packet::ParamsPacket;
+
+    let params = ParamsPacket::builder(20, 3, 2, 1).seed(1).build().unwrap();
+    let mut net = Network::for_message(params, 0);
+
+    // the source pushes full message to `t` others, telling them it has it
+    Rrs.on_receive(&mut net, Message::build_send_full_message(0, 0, 0), 0);
+    let pushed = net.drain_messages();
+    assert_eq!(pushed.len(), 3);
+    for message in &pushed {
+        assert_eq!(message.status, MessageStatus::FullMessage);
+        assert_eq!((message.from, message.hop_num), (0, 1));
+        assert!(message.to != 0 && message.bloomstatus.contains(0));
+    }
+
+    // past `k` hops only hashes go on
+    Rrs.on_receive(&mut net, Message::build_send_full_message(1, 2, 2), 200);
+    let hashes = net.drain_messages();
+    assert_eq!(hashes.len(), 3);
+    assert!(hashes
+        .iter()
+        .all(|m| m.status == MessageStatus::OnlyHash && m.hop_num == 3));
+
+    // a hash without the message is passed on and asked for, once per `send_ask_interval`
+    Rrs.on_receive(&mut net, Message::build_send_hash_message(2, 3, 3), 300);
+    let asked = net.drain_messages();
+    assert_eq!(asked.len(), 4);
+    assert_eq!(
+        asked
+            .iter()
+            .filter(|m| m.status == MessageStatus::AskForMessage)
+            .count(),
+        1
+    );
+    Rrs.on_receive(&mut net, Message::build_send_hash_message(4, 3, 3), 310);
+    assert_eq!(net.drain_messages().len(), 3);
+
+    // an ask for is answered with full message at hop 0 by a node that has it
+    Rrs.on_receive(&mut net, Message::build_send_query_message(3, 2), 400);
+    let reply = net.drain_messages();
+    assert_eq!(reply.len(), 1);
+    assert_eq!(reply[0], Message::build_send_full_message(2, 3, 0));
+    Rrs.on_receive(&mut net, Message::build_send_query_message(2, 5), 400);
+    assert!(net.drain_messages().is_empty());
+
+    // nodes known to have it are skipped
+    let mut message = Message::build_send_full_message(6, 7, 1);
+    (0..20).for_each(|node| message.bloomstatus.insert(node));
+    Rrs.on_receive(&mut net, message, 500);
+    assert!(net.drain_messages().is_empty());
+    let status = &net.node_status(0)[7];
+    assert_eq!(status.bloom_skip_count(), 3);
+    assert_eq!(status.false_positive_skip_count(), 0);
+
+    // nothing is spread after `each_handle_count` copies
+    for _ in 0..3 {
+        Rrs.on_receive(&mut net, Message::build_send_hash_message(4, 8, 1), 600);
+    }
+    net.drain_messages();
+    Rrs.on_receive(&mut net, Message::build_send_full_message(4, 8, 1), 600);
+    assert!(net.drain_messages().is_empty());
+}
+
+#[test]
+fn test_rrs_bloom_false_positives() {
+    use crate::{bloom::BloomKind, params_packet::ParamsPacket};
+
+    // one bit holds a single node, every other one looks like it has the message too
+    let bloom_filter = BloomKind::Bloom { bits: 1, hashes: 1 };
+    let params = ParamsPacket::builder(20, 3, 2, 1)
+        .seed(1)
+        .bloom_filter(bloom_filter)
+        .build()
+        .unwrap();
+    let mut net = Network::for_message(params, 0);
+    let mut message = Message::build_send_full_message(6, 7, 1);
+    message.bloomstatus = bloom_filter.new_filter();
+    message.bloomstatus.insert(19);
+    Rrs.on_receive(&mut net, message, 0);
+    assert!(net.drain_messages().is_empty());
+    let status = &net.node_status(0)[7];
+    assert_eq!(status.bloom_skip_count(), 3);
+    assert!(status.false_positive_skip_count() >= 2);
+}
+
+#[test]
+fn test_rrs_behaviours() {
+    use crate::{behaviour::SPAM_BUDGET, params_packet::ParamsPacket};
+
+    let params = ParamsPacket::builder(20, 3, 2, 1)
+        .seed(1)
+        .behaviours(
+            "dropper:0.2,no-reply:0.2,bloom-liar:0.2,spammer:0.2"
+                .parse()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let mut net = Network::for_message(params, 0);
+    let node_of = |net: &Network, behaviour| {
+        (0..20)
+            .find(|node| net.behaviours().of(*node) == behaviour)
+            .unwrap()
+    };
+
+    let dropper = node_of(&net, Behaviour::Dropper);
+    Rrs.on_receive(&mut net, Message::build_send_full_message(0, dropper, 1), 0);
+    assert!(net.drain_messages().is_empty());
+    assert_eq!(net.behaviours().swallowed_count(0), 1);
+
+    let no_reply = node_of(&net, Behaviour::NoReply);
+    Rrs.on_receive(
+        &mut net,
+        Message::build_send_full_message(0, no_reply, 1),
+        0,
+    );
+    net.drain_messages();
+    Rrs.on_receive(
+        &mut net,
+        Message::build_send_query_message(1, no_reply),
+        100,
+    );
+    assert!(net.drain_messages().is_empty());
+    assert_eq!(net.behaviours().unanswered_ask_for_count(0), 1);
+
+    let liar = node_of(&net, Behaviour::BloomLiar);
+    Rrs.on_receive(&mut net, Message::build_send_full_message(0, liar, 1), 0);
+    let lies = net.drain_messages();
+    assert!(lies.iter().all(|m| m.bloomstatus.len() == 20));
+    assert_eq!(net.behaviours().lie_count(0), lies.len() as u32);
+
+    // a spammer pushes full message past `k` hops, until its budget is spent
+    let spammer = node_of(&net, Behaviour::Spammer);
+    for _ in 0..SPAM_BUDGET {
+        Rrs.on_receive(&mut net, Message::build_send_full_message(0, spammer, 5), 0);
+        let spam = net.drain_messages();
+        assert_eq!(spam.len(), 3);
+        assert!(spam.iter().all(|m| m.status == MessageStatus::FullMessage));
+    }
+    Rrs.on_receive(&mut net, Message::build_send_full_message(0, spammer, 5), 0);
+    assert!(net.drain_messages().is_empty());
+    assert_eq!(net.behaviours().spam_count(0), 3 * SPAM_BUDGET);
+}
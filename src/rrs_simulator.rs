@@ -1,324 +1,267 @@
-use std::collections::HashSet;
-
 use rand::prelude::*;
 
 use crate::{
-    message::{Message, MessageStatus, NodeId},
-    message_queue::MessageQueue,
-    node_status::{NodeStatus, UpdateBloomFilter},
+    message::NodeId,
+    message_queue::{Event, TimeStamp},
+    network::Network,
+    params_packet::{ParamsError, ParamsPacket},
     performance_result::{summarize_data, ResultPack},
+    protocol::Protocol,
+    sim_rng::{
+        derive_message_seeds, new_rng, stream_rng, SimRng, ARRIVAL_STREAM, CONCURRENT_STREAM,
+    },
+    source::SourcePicker,
 };
 
-const MAX_HOP_NUM: u32 = 10;
-const EACH_HANDLE_COUNT: u32 = 3;
-// delay:
-const SEND_MESSAGE_DELAY_RANGE: (u32, u32) = (100, 120);
-const SEND_HASH_DELAY_RANGE: (u32, u32) = (100, 140);
-const SEND_ASK_FOR_DELAY_RANGE: (u32, u32) = (100, 130);
-const SEND_REPLY_ASK_DELAY_RANGE: (u32, u32) = (100, 130);
-const SEND_ASK_INTERVAL: u32 = 50;
-
-#[derive(Debug, Clone)]
-pub struct ParamsPacket {
-    /// network node scale
-    node_size: u32,
-    /// each round , node will select `t` neighbours and spread message
-    t: u32,
-    /// after message has been passed by `k` round, it will be transform into msg_hash header message and goes on.
-    k: u32,
-    /// simulate message count
-    n: u32,
-}
-
-impl ParamsPacket {
-    pub fn new(node_size: u32, t: u32, k: u32, n: u32) -> Self {
-        ParamsPacket { node_size, t, k, n }
-    }
-
-    pub fn node_size(&self) -> u32 {
-        self.node_size
-    }
-    pub fn t(&self) -> u32 {
-        self.t
-    }
-    pub fn k(&self) -> u32 {
-        self.k
-    }
-    pub fn n(&self) -> u32 {
-        self.n
-    }
-}
-
+/// Run the messages of a parameter set through `params.protocol` on one network.
 #[derive(Debug)]
 pub struct RRSSimulator {
-    /// All necessary params that was set at first time.
-    /// will not change while process.
-    params: ParamsPacket,
-    /// To simulator nodes spread a message. use rand() delay to sync nodes actions in one thread.
-    message_queue: MessageQueue,
-    /// To record nodes' status about this message.
-    node_status: Vec<NodeStatus>,
+    /// nodes, links, event queue and what is measured of each message.
+    network: Network,
+    /// how nodes react to the events.
+    protocol: Box<dyn Protocol>,
+    /// picks the source of each message.
+    source_picker: SourcePicker,
 }
 
-// pub struct
+// getter
+impl RRSSimulator {
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+}
 
 #[allow(non_snake_case)]
 impl RRSSimulator {
-    pub fn new(params: ParamsPacket) -> RRSSimulator {
-        let all_node_size: usize = params.node_size as usize;
-        RRSSimulator {
-            params,
-            message_queue: MessageQueue::new(),
-            node_status: vec![NodeStatus::new(all_node_size); all_node_size],
-        }
+    /// `Err` if `params` are not valid, their network could not be built or no node can be
+    /// the source.
+    pub fn new(params: ParamsPacket) -> Result<RRSSimulator, ParamsError> {
+        let protocol = params.protocol().build();
+        RRSSimulator::with_protocol(params, protocol)
     }
 
-    pub fn do_test(&mut self) {
-        let mut r = ResultPack::new(&self.params);
-
-        for _ in 0..self.params.n {
-            self.message_queue.reset_message_queue();
-            self.node_status.iter_mut().for_each(|ns| ns.reset_status());
-
-            // create a src broadcast message.
-            let message = Message::build_send_full_message(0, 0, 0);
-            self.message_queue.push(message, 0);
-
-            self.start_one_test();
+    /// run `protocol` instead of `params.protocol`, e.g. one defined outside this crate.
+    pub fn with_protocol(
+        params: ParamsPacket,
+        protocol: Box<dyn Protocol>,
+    ) -> Result<RRSSimulator, ParamsError> {
+        let network = Network::new(params)?;
+        let source_picker = network
+            .params()
+            .source()
+            .build(
+                network.params().latency(),
+                network.topology(),
+                network.latency(),
+            )
+            .map_err(ParamsError::InvalidSource)?;
+        Ok(RRSSimulator {
+            network,
+            protocol,
+            source_picker,
+        })
+    }
 
-            self.node_status.iter().for_each(|f| {
-                log::debug!(
-                    "recv_hash_count: {:?} recv_message_count:{:?} ",
-                    f.recv_hash_count(),
-                    f.recv_message_count(),
-                );
-            });
+    fn params(&self) -> &ParamsPacket {
+        self.network.params()
+    }
 
-            summarize_data(&mut r, &self.node_status, &self.message_queue);
-        }
+    /// simulate all `n` messages and show the result.
+    pub fn do_test(&mut self) -> ResultPack {
+        let r = self.run();
         r.show();
+        r
     }
 
-    fn start_one_test(&mut self) {
-        while let Some((message, ts)) = self.message_queue.pop_front() {
-            let send_node_id = message.to;
-            let mut send_node_status = self.node_status.get_mut(send_node_id).unwrap();
-            log::debug!("{} handle message {:?} at ts {}", send_node_id, message, ts);
-            let next_hop_num = message.hop_num + 1;
-            if next_hop_num > MAX_HOP_NUM {
-                continue;
+    /// simulate all `n` messages, each one with a sub seed derived from `params.seed`.
+    /// One after another on an idle network, or all sharing it if `arrival_rate` is set.
+    pub fn run(&mut self) -> ResultPack {
+        let mut r = ResultPack::new(self.params());
+        let message_seeds = derive_message_seeds(self.params().seed(), self.params().n());
+        self.protocol.start(&self.network);
+        if self.params().arrival_rate() > 0.0 {
+            self.simulate_concurrent(&message_seeds, &mut r);
+        } else {
+            for message_seed in message_seeds {
+                self.simulate_message(message_seed, &mut r);
             }
-            match message.status {
-                MessageStatus::FullMessage => {
-                    send_node_status.record_recv_message();
-
-                    if send_node_status.stop_handle_message(EACH_HANDLE_COUNT) {
-                        continue;
-                    }
-
-                    if next_hop_num <= self.params.k {
-                        let mut send_message = Message::build_send_full_message(
-                            send_node_id,
-                            send_node_id,
-                            next_hop_num,
-                        ); // set `to` later when push it in message queue
-
-                        // update send nodes' bloom status
-                        send_node_status.update_bloom_filter(&message.from);
-                        send_node_status.update_bloom_filter(&message.bloomstatus);
-
-                        // get dst list
-                        let dst_list = self.get_send_dst_list(
-                            send_node_id,
-                            &self.node_status[send_node_id].get_already_recvd_nodes(),
-                        );
-
-                        let mut send_node_status = self.node_status.get_mut(send_node_id).unwrap();
-                        // update send nodes' bloom status of ready to send nodes.
-                        send_node_status.update_bloom_filter(&dst_list);
-                        if dst_list.is_empty() {
-                            continue;
-                        }
-
-                        // update message's bloomfilter
-                        self.node_status[send_node_id]
-                            .get_already_recvd_nodes()
-                            .iter()
-                            .for_each(|dst| {
-                                send_message.bloomstatus.insert(*dst);
-                            });
-
-                        // push message into queue
-                        dst_list.iter().for_each(|dst| {
-                            send_message.to = *dst;
-                            let next_ts = random_delay(ts, SEND_MESSAGE_DELAY_RANGE);
-                            let res = self.message_queue.push(send_message.clone(), next_ts);
-
-                            log::debug!(
-                                "  -> send msg {:?} to {} ts: {}  res:{} queue_len:{}",
-                                send_message,
-                                *dst,
-                                next_ts,
-                                res,
-                                self.message_queue.len()
-                            );
-                        })
-                    } else if next_hop_num < MAX_HOP_NUM {
-                        // after k round, send msg hash
-                        let mut send_message = Message::build_send_hash_message(
-                            send_node_id,
-                            send_node_id,
-                            next_hop_num,
-                        );
-
-                        // get dst list
-                        let dst_list = self.get_send_dst_list(send_node_id, &HashSet::new());
+        }
+        r
+    }
 
-                        // push message into queue
-                        dst_list.iter().for_each(|dst| {
-                            send_message.to = *dst;
-                            let next_ts = random_delay(ts, SEND_HASH_DELAY_RANGE);
-                            let res = self.message_queue.push(send_message.clone(), next_ts);
-                            log::debug!(
-                                "  -> send hash {:?} to {} ts: {}  res:{} queue_len:{}",
-                                send_message,
-                                *dst,
-                                next_ts,
-                                res,
-                                self.message_queue.len()
-                            );
-                        })
-                    }
-                }
-                MessageStatus::OnlyHash => {
-                    send_node_status.record_recv_hash();
+    /// replay one message by its sub seed (as reported in `ResultData`), alone on an idle
+    /// network. So a message of a concurrent run is replayed without the contention it met,
+    /// and the protocol starts afresh without what it kept from earlier messages.
+    pub fn replay_message(&mut self, message_seed: u64) -> ResultPack {
+        let mut r = ResultPack::new(self.params());
+        self.protocol.start(&self.network);
+        self.simulate_message(message_seed, &mut r);
+        r
+    }
 
-                    if send_node_status.stop_handle_message(EACH_HANDLE_COUNT) {
-                        continue;
-                    }
+    fn simulate_message(&mut self, message_seed: u64, r: &mut ResultPack) {
+        let source = self.source_picker.pick(message_seed);
+        self.network
+            .reset(message_seed, new_rng(message_seed), 1, source);
+        self.protocol.reset(&self.network);
+        self.network.send_broadcast(message_seed, source, 0);
+
+        let end_ts = self.start_one_test();
+        self.network.advance_to(end_ts);
+
+        self.network.node_status(0).iter().for_each(|f| {
+            log::debug!(
+                "recv_hash_count: {:?} recv_message_count:{:?} ",
+                f.recv_hash_count(),
+                f.recv_message_count(),
+            );
+        });
+
+        summarize_data(r, 0, &self.network);
+    }
 
-                    let mut send_message =
-                        Message::build_send_hash_message(send_node_id, send_node_id, next_hop_num);
+    /// spread all messages at once, the i-th one with message id i, sent at Poisson arrivals
+    /// of `arrival_rate` from ts 0. They share links, failures, loss, partitions and behaviours,
+    /// which are drawn from the run seed, and only the first source is sure to be live and honest.
+    fn simulate_concurrent(&mut self, message_seeds: &[u64], r: &mut ResultPack) {
+        let seed = self.params().seed();
+        let sources: Vec<NodeId> = message_seeds
+            .iter()
+            .map(|message_seed| self.source_picker.pick(*message_seed))
+            .collect();
+        self.network.reset(
+            seed,
+            stream_rng(seed, CONCURRENT_STREAM),
+            message_seeds.len(),
+            sources[0],
+        );
+        self.protocol.reset(&self.network);
 
-                    // get dst list
-                    let dst_list = self.get_send_dst_list(send_node_id, &HashSet::new());
+        let arrivals = poisson_arrivals(
+            self.params().arrival_rate(),
+            message_seeds.len(),
+            &mut stream_rng(seed, ARRIVAL_STREAM),
+        );
+        for ((message_seed, source), start_ts) in message_seeds.iter().zip(sources).zip(arrivals) {
+            self.network.send_broadcast(*message_seed, source, start_ts);
+        }
 
-                    // push message into queue
-                    dst_list.iter().for_each(|dst| {
-                        send_message.to = *dst;
-                        let next_ts = random_delay(ts, SEND_HASH_DELAY_RANGE);
-                        let res = self.message_queue.push(send_message.clone(), next_ts);
-                        log::debug!(
-                            "  -> send hash {:?} to {} ts: {}  res:{} queue_len:{}",
-                            send_message,
-                            *dst,
-                            next_ts,
-                            res,
-                            self.message_queue.len()
-                        );
-                    });
+        let end_ts = self.start_one_test();
+        self.network.advance_to(end_ts);
 
-                    // ask for if not recvd
-                    if !self.node_status[send_node_id].has_recv_full_message() {
-                        if (self.node_status[send_node_id].send_ask_for_ts() == 0
-                            || ((self.node_status[send_node_id].send_ask_for_ts()
-                                + SEND_ASK_INTERVAL)
-                                < ts))
-                        {
-                            let send_ask_message = Message::build_send_query_message(
-                                send_node_id,
-                                get_random_neighbour(
-                                    send_node_id,
-                                    self.params.node_size as usize,
-                                    1,
-                                )[0],
-                            );
-                            let mut send_node_status =
-                                self.node_status.get_mut(send_node_id).unwrap();
-                            send_node_status.record_send_ask_for(ts);
-                            let next_ts = random_delay(ts, SEND_ASK_FOR_DELAY_RANGE);
-                            let res = self.message_queue.push(send_ask_message.clone(), next_ts);
-                            log::debug!(
-                                "  -> send ask for {:?} to {} ts: {}  res:{} queue_len:{}",
-                                send_ask_message,
-                                message.from,
-                                next_ts,
-                                res,
-                                self.message_queue.len()
-                            );
-                        }
-                    }
-                }
-                MessageStatus::AskForMessage => {
-                    if self.node_status[send_node_id].has_recv_full_message() {
-                        let send_message =
-                            Message::build_send_full_message(send_node_id, message.from, 0);
-                        let next_ts = random_delay(ts, SEND_REPLY_ASK_DELAY_RANGE);
-                        let res = self.message_queue.push(send_message.clone(), next_ts);
-                        log::debug!(
-                            "  -> send reply ask {:?} to {} ts: {}  res:{} queue_len:{}",
-                            send_message,
-                            message.from,
-                            next_ts,
-                            res,
-                            self.message_queue.len()
-                        );
-                    }
-                }
-            }
+        for message_id in 0..message_seeds.len() {
+            summarize_data(r, message_id, &self.network);
         }
     }
 
-    fn get_send_dst_list(&self, src_node_id: NodeId, bloomstatus: &HashSet<NodeId>) -> Vec<NodeId> {
-        let max_num = self.params.t;
-        let rand_neighbour = get_random_neighbour(
-            src_node_id,
-            self.params.node_size as usize,
-            max_num as usize,
-        );
-        rand_neighbour
-            .into_iter()
-            .filter(|d| {
-                if !bloomstatus.contains(&d) {
-                    true
-                } else {
-                    log::debug!("  filtered, wont send {}", d);
-                    false
-                }
-            })
-            .collect()
+    /// hand events to the protocol until none is left, return ts of the last one.
+    fn start_one_test(&mut self) -> TimeStamp {
+        let mut end_ts = 0;
+        while let Some((event, ts)) = self.network.next_event() {
+            end_ts = ts;
+            match event {
+                Event::Deliver(message) => self.protocol.on_receive(&mut self.network, message, ts),
+                Event::Timer(timer) => self.protocol.on_timer(&mut self.network, timer, ts),
+            }
+        }
+        end_ts
     }
 }
 
-/// get random `max_num` nodes from 0..`node_scale` apart from `src_node_id`
-fn get_random_neighbour(src_node_id: NodeId, node_scale: usize, max_num: usize) -> Vec<NodeId> {
-    let mut dst: Vec<NodeId> = (0..node_scale as usize).collect();
-    dst.remove(src_node_id);
-    let mut rng = rand::thread_rng();
-    dst.shuffle(&mut rng);
-    Vec::from_iter(dst[0..max_num as usize].iter().cloned())
-}
-
-fn random_delay(ori: u32, rg: (u32, u32)) -> u32 {
-    ori + (rand::random::<u32>()) % (rg.1 - rg.0) + rg.0
+/// ts of `count` arrivals of a Poisson process of `rate` per second, the first at 0.
+fn poisson_arrivals(rate: f64, count: usize, rng: &mut SimRng) -> Vec<TimeStamp> {
+    let mut ts = 0.0;
+    (0..count)
+        .map(|index| {
+            if index > 0 {
+                let u: f64 = rng.gen();
+                ts -= (1.0 - u).ln() / rate * 1000.0;
+            }
+            ts as TimeStamp
+        })
+        .collect()
 }
 
 #[test]
-fn test_get_random_neighbour() {
-    let r1 = get_random_neighbour(0, 30, 3);
-    let r2 = get_random_neighbour(0, 30, 3);
-    let r3 = get_random_neighbour(0, 30, 3);
-    println!("r1 is {:?}", r1);
-    println!("r2 is {:?}", r2);
-    println!("r2 is {:?}", r3);
-    assert_ne!(r1, r2);
-    assert_ne!(r1, r3);
-    assert_ne!(r2, r3);
+fn test_poisson_arrivals() {
+    let arrivals = poisson_arrivals(20.0, 1000, &mut new_rng(1));
+    assert_eq!(arrivals[0], 0);
+    assert!(arrivals.windows(2).all(|w| w[0] <= w[1]));
+    // 1000 arrivals of 20 per second take about 50 s
+    assert!((45_000..55_000).contains(&arrivals[999]));
+    assert_eq!(arrivals, poisson_arrivals(20.0, 1000, &mut new_rng(1)));
 }
 
+/// the whole run end to end: sequential, replayed, concurrent and with a custom protocol.
 #[test]
-fn test_random_delay() {
-    let range: (u32, u32) = (1, 10);
-    println!("{}", random_delay(1, range));
-    println!("{}", random_delay(10, range));
+fn test_run() {
+    use crate::{latency::DelayKind, message::Message, message_queue::Timer};
+
+    let base = ParamsPacket::builder(100, 3, 4, 10).seed(42);
+    let run = |params: ParamsPacket| RRSSimulator::new(params).unwrap().run();
+
+    // the same seed reproduces a run, another does not
+    let params = base.clone().build().unwrap();
+    let mut simulator = RRSSimulator::new(params.clone()).unwrap();
+    let rp = simulator.run();
+    assert_eq!(rp.each_result_data().len(), 10);
+    assert!(rp.avg_recv_node_size() > 90.0);
+    assert_eq!(run(params).each_result_data(), rp.each_result_data());
+    let other = run(base.clone().seed(43).build().unwrap());
+    assert_ne!(other.each_result_data(), rp.each_result_data());
+
+    // a message is replayed by its sub seed
+    let data = &rp.each_result_data()[7];
+    let replayed = simulator.replay_message(data.message_seed());
+    assert_eq!(replayed.each_result_data(), std::slice::from_ref(data));
+
+    // concurrent messages get their ids in order of arrival
+    let concurrent = base.clone().arrival_rate(20.0).build().unwrap();
+    let rp = run(concurrent.clone());
+    for (message_id, data) in rp.each_result_data().iter().enumerate() {
+        assert_eq!(data.message_id(), message_id);
+        assert!(data.recv_node_size() > 50);
+    }
+    assert!(rp
+        .each_result_data()
+        .windows(2)
+        .all(|w| w[0].start_ts() <= w[1].start_ts()));
+    assert_eq!(run(concurrent).each_result_data(), rp.each_result_data());
+
+    /// relays full message to one random neighbour 10 ms after first getting it.
+    #[derive(Debug)]
+    struct Relay;
+
+    impl Protocol for Relay {
+        fn on_receive(&mut self, net: &mut Network, message: Message, ts: TimeStamp) {
+            let status = net.node_status_mut(message.message_id, message.to);
+            if status.has_recv_full_message() {
+                return;
+            }
+            status.record_recv_message(ts);
+            let timer = Timer {
+                node: message.to,
+                message_id: message.message_id,
+                tag: 0,
+            };
+            net.set_timer(timer, ts + 10);
+        }
+
+        fn on_timer(&mut self, net: &mut Network, timer: Timer, ts: TimeStamp) {
+            for dst in net.random_neighbours(timer.node, 1) {
+                let mut message = Message::build_send_full_message(timer.node, dst, 1);
+                message.message_id = timer.message_id;
+                net.send(message, ts, DelayKind::Message);
+            }
+        }
+    }
+
+    let rp = RRSSimulator::with_protocol(base.build().unwrap(), Box::new(Relay))
+        .unwrap()
+        .run();
+    for data in rp.each_result_data() {
+        // every node that got it sends once, on top of the source's message to itself
+        assert_eq!(data.send_message_count(), data.recv_node_size() + 1);
+        assert!(data.mean_latency().unwrap() >= 110.0);
+    }
 }
@@ -0,0 +1,55 @@
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// rng used by the whole simulator. ChaCha output is stable across platforms and `rand` versions,
+/// so a `(seed, message_seed)` pair keeps reproducing the same run.
+pub type SimRng = ChaCha8Rng;
+
+pub fn new_rng(seed: u64) -> SimRng {
+    SimRng::seed_from_u64(seed)
+}
+
+/// rng on its own stream of `seed`, for randomness fixed for the whole run (like the topology),
+/// so it never shifts the per-message sub seeds.
+pub fn stream_rng(seed: u64, stream: u64) -> SimRng {
+    let mut rng = new_rng(seed);
+    rng.set_stream(stream);
+    rng
+}
+
+// Every model draws from a stream of its own, so adding or tuning one never shifts the draws of
+// the others, nor the protocol's own randomness. Failures, loss and behaviours use their stream
+// of the message seed when messages spread one after another, and of the run seed when they
+// spread concurrently and share one network. The source is always picked on the message seed,
+// the rest below on the run seed.
+pub const TOPOLOGY_STREAM: u64 = 1;
+pub const LATENCY_STREAM: u64 = 2;
+/// stream for node failures.
+pub const FAILURE_STREAM: u64 = 3;
+/// stream for packet loss.
+pub const LOSS_STREAM: u64 = 4;
+/// stream for node behaviours.
+pub const BEHAVIOUR_STREAM: u64 = 5;
+/// stream of each message seed for picking its source.
+pub const SOURCE_STREAM: u64 = 6;
+/// stream of the run seed for arrival ts of concurrent messages.
+pub const ARRIVAL_STREAM: u64 = 7;
+/// stream of the run seed for the protocol while messages spread concurrently.
+pub const CONCURRENT_STREAM: u64 = 8;
+/// stream of the run seed for protocol state kept across messages.
+pub const PROTOCOL_STREAM: u64 = 9;
+
+/// derive `n` per-message sub seeds from the run seed.
+/// Each simulated message uses its own rng seeded by its sub seed, so it can be replayed alone.
+pub fn derive_message_seeds(seed: u64, n: u32) -> Vec<u64> {
+    let mut seed_rng = new_rng(seed);
+    (0..n).map(|_| seed_rng.next_u64()).collect()
+}
+
+#[test]
+fn test_derive_message_seeds() {
+    let s1 = derive_message_seeds(7, 10);
+    let s2 = derive_message_seeds(7, 20);
+    assert_eq!(s1[..], s2[..10]);
+    assert_ne!(derive_message_seeds(8, 10), s1);
+}
@@ -0,0 +1,228 @@
+use std::{fmt, str::FromStr};
+
+use rand::{distributions::WeightedIndex, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    latency::{LatencyKind, LatencyModel},
+    message::NodeId,
+    sim_rng::{stream_rng, SOURCE_STREAM},
+    topology::Topology,
+};
+
+/// Which node broadcasts each simulated message.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub enum SourceKind {
+    /// always the same node.
+    Fixed(NodeId),
+    /// any node, uniformly.
+    Uniform,
+    /// a node drawn with probability proportional to `degree ^ exponent`, nodes without
+    /// neighbours never. A negative exponent favours poorly connected nodes.
+    Degree { exponent: f64 },
+    /// any node of the named region of the `regions` latency model, uniformly.
+    Region(String),
+}
+
+impl Default for SourceKind {
+    fn default() -> Self {
+        SourceKind::Fixed(0)
+    }
+}
+
+impl SourceKind {
+    pub fn validate(&self, node_size: u32, latency: &LatencyKind) -> Result<(), String> {
+        match self {
+            SourceKind::Fixed(node) if *node >= node_size as usize => {
+                Err(format!("{}: source out of node_size {}", self, node_size))
+            }
+            SourceKind::Degree { exponent } if !exponent.is_finite() => {
+                Err(format!("{}: exponent must be finite", self))
+            }
+            SourceKind::Region(name) => match latency {
+                LatencyKind::Regions(table) => {
+                    if table.regions().iter().any(|r| r.name == *name) {
+                        Ok(())
+                    } else {
+                        Err(format!("{}: no region `{}` in {}", self, name, latency))
+                    }
+                }
+                _ => Err(format!("{}: needs the regions latency model", self)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// `Err` if no node can be the source, or the weights of the nodes overflow.
+    pub fn build(
+        &self,
+        latency_kind: &LatencyKind,
+        topology: &dyn Topology,
+        latency: &dyn LatencyModel,
+    ) -> Result<SourcePicker, String> {
+        let node_size = topology.node_size();
+        let weights: Vec<f64> = match self {
+            SourceKind::Fixed(node) => return Ok(SourcePicker::Fixed(*node)),
+            SourceKind::Uniform => vec![1.0; node_size],
+            SourceKind::Degree { exponent } => (0..node_size)
+                .map(|node| match topology.degree(node) {
+                    0 => 0.0,
+                    degree => (degree as f64).powf(*exponent),
+                })
+                .collect(),
+            SourceKind::Region(name) => {
+                let region = match latency_kind {
+                    LatencyKind::Regions(table) => {
+                        table.regions().iter().position(|r| r.name == *name)
+                    }
+                    _ => None,
+                };
+                (0..node_size)
+                    .map(|node| {
+                        (region.is_some() && latency.region_of(node) == region) as u32 as f64
+                    })
+                    .collect()
+            }
+        };
+        if !weights.iter().sum::<f64>().is_finite() {
+            return Err(format!("{}: node weights overflow", self));
+        }
+        let dist = WeightedIndex::new(&weights)
+            .map_err(|e| format!("{}: no node can be the source: {}", self, e))?;
+        Ok(SourcePicker::Weighted(dist))
+    }
+}
+
+/// `fixed:<node>`, `uniform`, `degree:<exponent>` or `region:<name>`.
+impl FromStr for SourceKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "uniform" => Ok(SourceKind::Uniform),
+            Some(("fixed", node)) => Ok(SourceKind::Fixed(
+                node.parse().map_err(|e| format!("{} in `{}`", e, s))?,
+            )),
+            Some(("degree", exponent)) => Ok(SourceKind::Degree {
+                exponent: exponent.parse().map_err(|e| format!("{} in `{}`", e, s))?,
+            }),
+            Some(("region", name)) if !name.is_empty() => Ok(SourceKind::Region(name.to_string())),
+            _ => Err(format!(
+                "unknown source `{}`, expect fixed:<node>, uniform, degree:<exponent> or region:<name>",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for SourceKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for SourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceKind::Fixed(node) => write!(f, "fixed:{}", node),
+            SourceKind::Uniform => write!(f, "uniform"),
+            SourceKind::Degree { exponent } => write!(f, "degree:{}", exponent),
+            SourceKind::Region(name) => write!(f, "region:{}", name),
+        }
+    }
+}
+
+/// Pick the source of each message.
+#[derive(Debug, Clone)]
+pub enum SourcePicker {
+    Fixed(NodeId),
+    Weighted(WeightedIndex<f64>),
+}
+
+impl SourcePicker {
+    pub fn pick(&self, message_seed: u64) -> NodeId {
+        match self {
+            SourcePicker::Fixed(node) => *node,
+            SourcePicker::Weighted(dist) => {
+                dist.sample(&mut stream_rng(message_seed, SOURCE_STREAM))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_source_kind() {
+    assert_eq!("fixed:3".parse(), Ok(SourceKind::Fixed(3)));
+    assert_eq!("uniform".parse(), Ok(SourceKind::Uniform));
+    assert_eq!(
+        "degree:-1".parse(),
+        Ok(SourceKind::Degree { exponent: -1.0 })
+    );
+    assert_eq!(
+        "region:eu".parse(),
+        Ok(SourceKind::Region("eu".to_string()))
+    );
+    for s in ["fixed:0", "uniform", "degree:2", "region:eu"] {
+        assert_eq!(s.parse::<SourceKind>().unwrap().to_string(), s);
+    }
+    assert!("fixed".parse::<SourceKind>().is_err());
+    assert!(SourceKind::Fixed(10)
+        .validate(10, &LatencyKind::Uniform)
+        .is_err());
+    assert!(SourceKind::Region("eu".to_string())
+        .validate(10, &LatencyKind::Uniform)
+        .is_err());
+}
+
+#[test]
+fn test_pick_source() {
+    use crate::{params_packet::ParamsPacket, sim_rng::new_rng, topology::TopologyKind};
+
+    let params = ParamsPacket::builder(50, 3, 3, 1).build().unwrap();
+    let mut rng = new_rng(1);
+    let star = TopologyKind::BarabasiAlbert { m: 1 }
+        .build(50, &mut rng)
+        .unwrap();
+    let latency = LatencyKind::Uniform.build(&params, &mut rng).unwrap();
+
+    let fixed = SourceKind::Fixed(7)
+        .build(&LatencyKind::Uniform, &*star, &*latency)
+        .unwrap();
+    assert!((0..20).all(|seed| fixed.pick(seed) == 7));
+
+    let uniform = SourceKind::Uniform
+        .build(&LatencyKind::Uniform, &*star, &*latency)
+        .unwrap();
+    let sources: Vec<NodeId> = (0..200).map(|seed| uniform.pick(seed)).collect();
+    assert!(
+        sources
+            .iter()
+            .collect::<std::collections::HashSet<_>>()
+            .len()
+            > 30
+    );
+    assert_eq!(uniform.pick(5), uniform.pick(5));
+
+    // hubs are picked far more often than with the inverse
+    let mean_degree = |kind: SourceKind| {
+        let picker = kind
+            .build(&LatencyKind::Uniform, &*star, &*latency)
+            .unwrap();
+        (0..500)
+            .map(|seed| star.degree(picker.pick(seed)) as f64)
+            .sum::<f64>()
+            / 500.0
+    };
+    assert!(
+        mean_degree(SourceKind::Degree { exponent: 1.0 })
+            > 2.0 * mean_degree(SourceKind::Degree { exponent: -1.0 })
+    );
+
+    // hub weights overflow
+    assert!(SourceKind::Degree { exponent: 400.0 }
+        .build(&LatencyKind::Uniform, &*star, &*latency)
+        .is_err());
+}
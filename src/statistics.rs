@@ -0,0 +1,158 @@
+use serde::Serialize;
+
+/// descriptive statistics of one metric over the simulated messages.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Summary {
+    /// how many messages have this metric
+    pub count: usize,
+    pub mean: f64,
+    /// sample standard deviation
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub median: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub p75: f64,
+    pub p95: f64,
+    /// 95% confidence interval of the mean (student's t)
+    pub ci95_lo: f64,
+    pub ci95_hi: f64,
+}
+
+impl Summary {
+    /// `None` for no values.
+    pub fn from_values(values: &[f64]) -> Option<Summary> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let std_dev = if count > 1 {
+            (sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        let half_width = if count > 1 {
+            t_critical_95(count - 1) * std_dev / (count as f64).sqrt()
+        } else {
+            0.0
+        };
+        Some(Summary {
+            count,
+            mean,
+            std_dev,
+            min: sorted[0],
+            max: sorted[count - 1],
+            median: median(&sorted),
+            p5: percentile(&sorted, 5.0).unwrap(),
+            p25: percentile(&sorted, 25.0).unwrap(),
+            p75: percentile(&sorted, 75.0).unwrap(),
+            p95: percentile(&sorted, 95.0).unwrap(),
+            ci95_lo: mean - half_width,
+            ci95_hi: mean + half_width,
+        })
+    }
+
+    /// half width of the 95% confidence interval.
+    pub fn ci95_half_width(&self) -> f64 {
+        (self.ci95_hi - self.ci95_lo) / 2.0
+    }
+
+    /// `mean ± ci95 half width`, as shown in markdown tables.
+    pub fn format_mean_ci(&self) -> String {
+        format!("{:.2} ± {:.2}", self.mean, self.ci95_half_width())
+    }
+}
+
+/// nearest-rank percentile of a sorted slice.
+pub fn percentile<T: Copy>(sorted: &[T], p: f64) -> Option<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+#[allow(clippy::manual_is_multiple_of)]
+fn median(sorted: &[f64]) -> f64 {
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+/// two-sided 95% critical value of student's t distribution. Tabulated up to 30 degrees of
+/// freedom, past that the Cornish-Fisher expansion around the normal quantile, good to 1e-4.
+fn t_critical_95(degrees_of_freedom: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    const Z: f64 = 1.959964;
+    match degrees_of_freedom {
+        0 => f64::INFINITY,
+        df if df <= TABLE.len() => TABLE[df - 1],
+        df => {
+            let df = df as f64;
+            let g1 = (Z.powi(3) + Z) / 4.0;
+            let g2 = (5.0 * Z.powi(5) + 16.0 * Z.powi(3) + 3.0 * Z) / 96.0;
+            let g3 = (3.0 * Z.powi(7) + 19.0 * Z.powi(5) + 17.0 * Z.powi(3) - 15.0 * Z) / 384.0;
+            Z + g1 / df + g2 / df.powi(2) + g3 / df.powi(3)
+        }
+    }
+}
+
+#[test]
+fn test_percentile() {
+    let sorted: Vec<u32> = (1..=100).collect();
+    assert_eq!(percentile(&sorted, 50.0), Some(50));
+    assert_eq!(percentile(&sorted, 99.0), Some(99));
+    assert_eq!(percentile(&sorted, 100.0), Some(100));
+    assert_eq!(percentile(&sorted, 0.0), Some(1));
+    assert_eq!(percentile(&[7_u32], 90.0), Some(7));
+    assert_eq!(percentile::<u32>(&[], 50.0), None);
+}
+
+#[test]
+fn test_summary() {
+    assert_eq!(Summary::from_values(&[]), None);
+
+    let s = Summary::from_values(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+    assert_eq!(s.count, 8);
+    assert_eq!(s.mean, 5.0);
+    assert!((s.std_dev - 2.138).abs() < 1e-3);
+    assert_eq!(s.min, 2.0);
+    assert_eq!(s.max, 9.0);
+    assert_eq!(s.median, 4.5);
+    // 2.365 * 2.138 / sqrt(8)
+    assert!((s.ci95_half_width() - 1.788).abs() < 1e-3);
+    assert!(s.ci95_lo < s.mean && s.mean < s.ci95_hi);
+
+    let single = Summary::from_values(&[3.0]).unwrap();
+    assert_eq!(single.std_dev, 0.0);
+    assert_eq!(single.ci95_lo, 3.0);
+    assert_eq!(single.ci95_hi, 3.0);
+}
+
+#[test]
+fn test_t_critical_95() {
+    assert_eq!(t_critical_95(30), 2.042);
+    // just past the table, and the usual table rows further out
+    for (df, t) in [
+        (31, 2.0395),
+        (40, 2.0211),
+        (60, 2.0003),
+        (120, 1.9799),
+        (1000, 1.9623),
+    ] {
+        assert!((t_critical_95(df) - t).abs() < 1e-4, "df {}", df);
+    }
+    assert!(t_critical_95(31) < t_critical_95(30));
+}
@@ -0,0 +1,401 @@
+use std::{fmt, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{
+    behaviour::BehaviourMix,
+    bloom::BloomKind,
+    latency::LatencyKind,
+    loss::LossKind,
+    params_packet::{ParamsPacket, ParamsPacketBuilder},
+    partition::PartitionKind,
+    protocol::ProtocolKind,
+    source::SourceKind,
+    topology::TopologyKind,
+};
+
+/// theoretical coverage of spreading full message `k` rounds with fanout `t`:
+/// `(t^(k+1) - 1) / (t - 1) / node_size`
+pub fn theory_coverage(node_size: u32, t: u32, k: u32) -> f64 {
+    (f64::powi(t as f64, (k + 1) as i32) - 1.0) / (t - 1) as f64 / node_size as f64
+}
+
+/// one sweep dimension: a single value, a list, or an inclusive range.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum ValueSpec {
+    Single(u32),
+    List(Vec<u32>),
+    Range {
+        start: u32,
+        /// inclusive
+        end: u32,
+        #[serde(default = "default_step")]
+        step: u32,
+    },
+}
+
+fn default_step() -> u32 {
+    1
+}
+
+impl ValueSpec {
+    pub fn validate(&self, name: &str) -> Result<(), String> {
+        match self {
+            ValueSpec::Range { step: 0, .. } => Err(format!("{}: range step must not be 0", name)),
+            _ => Ok(()),
+        }
+    }
+
+    /// panics on a range of step 0, which [`ValueSpec::validate`] rejects.
+    pub fn values(&self) -> Vec<u32> {
+        match self {
+            ValueSpec::Single(v) => vec![*v],
+            ValueSpec::List(list) => list.clone(),
+            ValueSpec::Range { start, end, step } => {
+                (*start..=*end).step_by(*step as usize).collect()
+            }
+        }
+    }
+}
+
+/// one value or a list of values of a non numeric sweep dimension.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T: Clone> OneOrMany<T> {
+    pub fn values(&self) -> Vec<T> {
+        match self {
+            OneOrMany::One(v) => vec![v.clone()],
+            OneOrMany::Many(list) => list.clone(),
+        }
+    }
+}
+
+/// skip parameter sets whose theoretical coverage is out of `[theory_min, theory_max]`.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SweepFilter {
+    pub theory_min: Option<f64>,
+    pub theory_max: Option<f64>,
+}
+
+impl SweepFilter {
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn accept(&self, node_size: u32, t: u32, k: u32) -> bool {
+        if self.theory_min.is_none() && self.theory_max.is_none() {
+            return true;
+        }
+        let theory_value = theory_coverage(node_size, t, k);
+        self.theory_min.map_or(true, |min| theory_value >= min)
+            && self.theory_max.map_or(true, |max| theory_value <= max)
+    }
+}
+
+/// Declarative description of an experiment, read from a `.toml` or `.json` file.
+///
+/// ```toml
+/// n = 100
+/// node_size = { start = 100, end = 590, step = 10 }
+/// t = { start = 3, end = 8 }
+/// k = [2, 3, 4, 5, 6, 7]
+///
+/// [filter]
+/// theory_min = 0.7
+/// theory_max = 4.0
+/// ```
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SweepSpec {
+    /// simulate message count of each parameter set
+    pub n: u32,
+    pub node_size: ValueSpec,
+    pub t: ValueSpec,
+    pub k: ValueSpec,
+    pub max_hop_num: Option<ValueSpec>,
+    pub each_handle_count: Option<ValueSpec>,
+    pub send_ask_interval: Option<ValueSpec>,
+    pub payload_size: Option<ValueSpec>,
+    /// kbit/s, `0` means unlimited
+    pub upload_bandwidth: Option<ValueSpec>,
+    pub download_bandwidth: Option<ValueSpec>,
+    pub send_message_delay_range: Option<(u32, u32)>,
+    pub send_hash_delay_range: Option<(u32, u32)>,
+    pub send_ask_for_delay_range: Option<(u32, u32)>,
+    pub send_reply_ask_delay_range: Option<(u32, u32)>,
+    pub seed: Option<u64>,
+    /// e.g. `"complete"` or `["regular:8", "ws:6:0.1"]`
+    pub topology: Option<OneOrMany<TopologyKind>>,
+    /// e.g. `"uniform"` or `["uniform", "regions:latency/regions.toml"]`
+    pub latency: Option<OneOrMany<LatencyKind>>,
+    /// e.g. `["exact", "bloom:1024:3"]`
+    pub bloom_filter: Option<OneOrMany<BloomKind>>,
+    pub offline_fraction: Option<OneOrMany<f64>>,
+    /// crash/join toggles per node per second
+    pub churn_rate: Option<OneOrMany<f64>>,
+    /// e.g. `["none", "bernoulli:0.05", "ge:0.01:0.3"]`
+    pub packet_loss: Option<OneOrMany<LossKind>>,
+    /// e.g. `["none", "split:2:200:1200"]`
+    pub partitions: Option<OneOrMany<PartitionKind>>,
+    /// e.g. `["honest", "dropper:0.1", "bloom-liar:0.05,spammer:0.05"]`
+    pub behaviours: Option<OneOrMany<BehaviourMix>>,
+    /// e.g. `["fixed:0", "uniform", "degree:1"]`
+    pub source: Option<OneOrMany<SourceKind>>,
+    /// messages per second sent concurrently, e.g. `[0, 5, 20]`
+    pub arrival_rate: Option<OneOrMany<f64>>,
+    /// e.g. `["rrs", "flood", "push:6", "plumtree", "gossipsub:score", "erasure:4:8"]`
+    pub protocol: Option<OneOrMany<ProtocolKind>>,
+    #[serde(default)]
+    pub filter: SweepFilter,
+}
+
+impl SweepSpec {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SweepSpec, SweepError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(SweepError::Io)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => SweepSpec::from_json(&content),
+            _ => SweepSpec::from_toml(&content),
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<SweepSpec, SweepError> {
+        let spec: SweepSpec = toml::from_str(content).map_err(SweepError::Toml)?;
+        spec.validate().map_err(SweepError::Invalid)?;
+        Ok(spec)
+    }
+
+    pub fn from_json(content: &str) -> Result<SweepSpec, SweepError> {
+        let spec: SweepSpec = serde_json::from_str(content).map_err(SweepError::Json)?;
+        spec.validate().map_err(SweepError::Invalid)?;
+        Ok(spec)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let dims = [
+            ("node_size", Some(&self.node_size)),
+            ("t", Some(&self.t)),
+            ("k", Some(&self.k)),
+            ("max_hop_num", self.max_hop_num.as_ref()),
+            ("each_handle_count", self.each_handle_count.as_ref()),
+            ("send_ask_interval", self.send_ask_interval.as_ref()),
+            ("payload_size", self.payload_size.as_ref()),
+            ("upload_bandwidth", self.upload_bandwidth.as_ref()),
+            ("download_bandwidth", self.download_bandwidth.as_ref()),
+        ];
+        for (name, spec) in dims {
+            if let Some(spec) = spec {
+                spec.validate(name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// expand the spec into every accepted parameter set, in `node_size`, `t`, `k`, ... order.
+    /// Sets rejected by [`ParamsPacket::validate`] are skipped.
+    pub fn params_list(&self) -> Vec<ParamsPacket> {
+        let mut builders = Vec::new();
+        for node_size in self.node_size.values() {
+            for t in self.t.values() {
+                for k in self.k.values() {
+                    if self.filter.accept(node_size, t, k) {
+                        builders.push(ParamsPacket::builder(node_size, t, k, self.n));
+                    }
+                }
+            }
+        }
+
+        builders = expand(builders, &self.max_hop_num, |b, v| b.max_hop_num(v));
+        builders = expand(builders, &self.each_handle_count, |b, v| {
+            b.each_handle_count(v)
+        });
+        builders = expand(builders, &self.send_ask_interval, |b, v| {
+            b.send_ask_interval(v)
+        });
+        builders = expand(builders, &self.payload_size, |b, v| b.payload_size(v));
+        builders = expand(builders, &self.upload_bandwidth, |b, v| {
+            b.upload_bandwidth(v)
+        });
+        builders = expand(builders, &self.download_bandwidth, |b, v| {
+            b.download_bandwidth(v)
+        });
+        let topology = self.topology.as_ref().map(|t| t.values());
+        builders = expand_values(builders, &topology, |b, v| b.topology(v));
+        let latency = self.latency.as_ref().map(|l| l.values());
+        builders = expand_values(builders, &latency, |b, v| b.latency(v));
+        let bloom_filter = self.bloom_filter.as_ref().map(|b| b.values());
+        builders = expand_values(builders, &bloom_filter, |b, v| b.bloom_filter(v));
+        let offline_fraction = self.offline_fraction.as_ref().map(|f| f.values());
+        builders = expand_values(builders, &offline_fraction, |b, v| b.offline_fraction(v));
+        let churn_rate = self.churn_rate.as_ref().map(|c| c.values());
+        builders = expand_values(builders, &churn_rate, |b, v| b.churn_rate(v));
+        let packet_loss = self.packet_loss.as_ref().map(|l| l.values());
+        builders = expand_values(builders, &packet_loss, |b, v| b.packet_loss(v));
+        let partitions = self.partitions.as_ref().map(|p| p.values());
+        builders = expand_values(builders, &partitions, |b, v| b.partitions(v));
+        let behaviours = self.behaviours.as_ref().map(|b| b.values());
+        builders = expand_values(builders, &behaviours, |b, v| b.behaviours(v));
+        let source = self.source.as_ref().map(|s| s.values());
+        builders = expand_values(builders, &source, |b, v| b.source(v));
+        let arrival_rate = self.arrival_rate.as_ref().map(|a| a.values());
+        builders = expand_values(builders, &arrival_rate, |b, v| b.arrival_rate(v));
+        let protocol = self.protocol.as_ref().map(|p| p.values());
+        builders = expand_values(builders, &protocol, |b, v| b.protocol(v));
+
+        builders
+            .into_iter()
+            .map(|mut builder| {
+                if let Some(v) = self.send_message_delay_range {
+                    builder = builder.send_message_delay_range(v);
+                }
+                if let Some(v) = self.send_hash_delay_range {
+                    builder = builder.send_hash_delay_range(v);
+                }
+                if let Some(v) = self.send_ask_for_delay_range {
+                    builder = builder.send_ask_for_delay_range(v);
+                }
+                if let Some(v) = self.send_reply_ask_delay_range {
+                    builder = builder.send_reply_ask_delay_range(v);
+                }
+                if let Some(v) = self.seed {
+                    builder = builder.seed(v);
+                }
+                builder.build()
+            })
+            .filter_map(|params| match params {
+                Ok(params) => Some(params),
+                Err(e) => {
+                    log::warn!("skip parameter set: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// cross every builder with every value of an optional sweep dimension.
+fn expand<F>(
+    builders: Vec<ParamsPacketBuilder>,
+    spec: &Option<ValueSpec>,
+    set: F,
+) -> Vec<ParamsPacketBuilder>
+where
+    F: Fn(ParamsPacketBuilder, u32) -> ParamsPacketBuilder,
+{
+    let values = spec.as_ref().map(|spec| spec.values());
+    expand_values(builders, &values, set)
+}
+
+fn expand_values<T: Clone, F>(
+    builders: Vec<ParamsPacketBuilder>,
+    values: &Option<Vec<T>>,
+    set: F,
+) -> Vec<ParamsPacketBuilder>
+where
+    F: Fn(ParamsPacketBuilder, T) -> ParamsPacketBuilder,
+{
+    match values {
+        None => builders,
+        Some(values) => builders
+            .into_iter()
+            .flat_map(|builder| {
+                values
+                    .iter()
+                    .map(|v| set(builder.clone(), v.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect(),
+    }
+}
+
+#[derive(Debug)]
+pub enum SweepError {
+    Io(std::io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SweepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SweepError::Io(e) => write!(f, "read sweep spec failed: {}", e),
+            SweepError::Toml(e) => write!(f, "parse toml sweep spec failed: {}", e),
+            SweepError::Json(e) => write!(f, "parse json sweep spec failed: {}", e),
+            SweepError::Invalid(reason) => write!(f, "invalid sweep spec: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SweepError {}
+
+#[test]
+fn test_value_spec() {
+    assert_eq!(ValueSpec::Single(3).values(), vec![3]);
+    assert_eq!(ValueSpec::List(vec![5, 3]).values(), vec![5, 3]);
+    assert_eq!(
+        ValueSpec::Range {
+            start: 100,
+            end: 130,
+            step: 10
+        }
+        .values(),
+        vec![100, 110, 120, 130]
+    );
+    assert!(SweepSpec::from_toml(
+        "n = 1\nnode_size = 100\nt = { start = 3, end = 8, step = 0 }\nk = 4"
+    )
+    .is_err());
+}
+
+#[test]
+fn test_parse_and_expand_spec() {
+    let spec = SweepSpec::from_toml(
+        r#"
+        n = 10
+        node_size = { start = 100, end = 120, step = 10 }
+        t = [3, 4]
+        k = { start = 2, end = 5 }
+        max_hop_num = [8, 10]
+        seed = 7
+        topology = ["complete", "regular:6"]
+
+        [filter]
+        theory_min = 0.7
+        theory_max = 4.0
+        "#,
+    )
+    .unwrap();
+
+    let params_list = spec.params_list();
+    assert!(!params_list.is_empty());
+    for params in &params_list {
+        let theory_value = theory_coverage(params.node_size(), params.t(), params.k());
+        assert!((0.7..=4.0).contains(&theory_value));
+        assert_eq!(params.seed(), 7);
+        assert_eq!(params.n(), 10);
+    }
+    assert_eq!(
+        params_list
+            .iter()
+            .filter(|p| p.node_size() == 100 && p.t() == 3 && p.k() == 4)
+            .count(),
+        4
+    );
+    assert!(params_list
+        .iter()
+        .any(|p| p.topology() == TopologyKind::RandomRegular { degree: 6 }));
+
+    let json_spec = SweepSpec::from_json(
+        r#"{"n": 10, "node_size": {"start": 100, "end": 120, "step": 10}, "t": [3, 4],
+            "k": {"start": 2, "end": 5}, "max_hop_num": [8, 10], "seed": 7,
+            "topology": ["complete", "regular:6"],
+            "filter": {"theory_min": 0.7, "theory_max": 4.0}}"#,
+    )
+    .unwrap();
+    assert_eq!(json_spec, spec);
+}
@@ -0,0 +1,498 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    kademlia::{KadSelection, KademliaTopology},
+    message::NodeId,
+    sim_rng::SimRng,
+};
+
+/// Which nodes each node can send to. Built once per run and shared by every simulated message.
+pub trait Topology: fmt::Debug {
+    fn node_size(&self) -> usize;
+
+    /// all nodes `node` can send to.
+    fn neighbours(&self, node: NodeId) -> Vec<NodeId>;
+
+    fn degree(&self, node: NodeId) -> usize {
+        self.neighbours(node).len()
+    }
+
+    /// `max_num` distinct random neighbours of `node`, fewer if its degree is smaller.
+    fn random_neighbours(&self, node: NodeId, max_num: usize, rng: &mut SimRng) -> Vec<NodeId> {
+        let mut neighbours = self.neighbours(node);
+        neighbours.shuffle(rng);
+        neighbours.truncate(max_num);
+        neighbours
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(try_from = "String")]
+pub enum TopologyKind {
+    /// every node can reach all other `node_size - 1` nodes.
+    #[default]
+    Complete,
+    /// random graph in which every node has exactly `degree` neighbours.
+    RandomRegular { degree: u32 },
+    /// Erdős–Rényi G(n, p): each pair of nodes is linked with probability `p`.
+    ErdosRenyi { p: f64 },
+    /// Watts–Strogatz small world: ring lattice of `k` neighbours, each link rewired with probability `beta`.
+    WattsStrogatz { k: u32, beta: f64 },
+    /// Barabási–Albert scale free: each new node attaches to `m` nodes by preferential attachment.
+    BarabasiAlbert { m: u32 },
+    /// Kademlia routing table: random 256-bit keys, up to `bucket_size` peers per k-bucket.
+    Kademlia {
+        bucket_size: u32,
+        selection: KadSelection,
+    },
+}
+
+impl TopologyKind {
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn validate(&self, node_size: u32) -> Result<(), String> {
+        match *self {
+            TopologyKind::Complete => Ok(()),
+            TopologyKind::RandomRegular { degree } => {
+                if degree == 0 || degree >= node_size {
+                    Err(format!("regular degree must be in 1..{}", node_size))
+                } else if degree as u64 * node_size as u64 % 2 != 0 {
+                    Err("regular degree * node_size must be even".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            TopologyKind::ErdosRenyi { p } => {
+                if p > 0.0 && p <= 1.0 {
+                    Ok(())
+                } else {
+                    Err("erdos-renyi p must be in (0, 1]".to_string())
+                }
+            }
+            TopologyKind::WattsStrogatz { k, beta } => {
+                if k < 2 || k % 2 != 0 || k >= node_size {
+                    Err(format!(
+                        "watts-strogatz k must be even and in 2..{}",
+                        node_size
+                    ))
+                } else if !(0.0..=1.0).contains(&beta) {
+                    Err("watts-strogatz beta must be in [0, 1]".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            TopologyKind::BarabasiAlbert { m } => {
+                if m == 0 || m >= node_size {
+                    Err(format!("barabasi-albert m must be in 1..{}", node_size))
+                } else {
+                    Ok(())
+                }
+            }
+            TopologyKind::Kademlia { bucket_size, .. } => {
+                if bucket_size == 0 {
+                    Err("kademlia bucket size must be at least 1".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// `Err` if no graph of this kind could be drawn.
+    pub fn build(&self, node_size: usize, rng: &mut SimRng) -> Result<Box<dyn Topology>, String> {
+        Ok(match *self {
+            TopologyKind::Complete => Box::new(CompleteTopology { node_size }),
+            TopologyKind::RandomRegular { degree } => {
+                Box::new(random_regular(node_size, degree as usize, rng)?)
+            }
+            TopologyKind::ErdosRenyi { p } => Box::new(erdos_renyi(node_size, p, rng)),
+            TopologyKind::WattsStrogatz { k, beta } => {
+                Box::new(watts_strogatz(node_size, k as usize, beta, rng))
+            }
+            TopologyKind::BarabasiAlbert { m } => {
+                Box::new(barabasi_albert(node_size, m as usize, rng))
+            }
+            TopologyKind::Kademlia {
+                bucket_size,
+                selection,
+            } => Box::new(KademliaTopology::new(
+                node_size,
+                bucket_size as usize,
+                selection,
+                rng,
+            )),
+        })
+    }
+}
+
+/// `complete`, `regular:<degree>`, `er:<p>`, `ws:<k>:<beta>`, `ba:<m>`
+/// or `kademlia:<bucket_size>[:uniform|per-bucket]`
+impl FromStr for TopologyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let parse_err = |e: &dyn fmt::Display| format!("invalid topology `{}`: {}", s, e);
+        match parts.as_slice() {
+            ["complete"] => Ok(TopologyKind::Complete),
+            ["regular", degree] => Ok(TopologyKind::RandomRegular {
+                degree: degree.parse().map_err(|e| parse_err(&e))?,
+            }),
+            ["er", p] => Ok(TopologyKind::ErdosRenyi {
+                p: p.parse().map_err(|e| parse_err(&e))?,
+            }),
+            ["ws", k, beta] => Ok(TopologyKind::WattsStrogatz {
+                k: k.parse().map_err(|e| parse_err(&e))?,
+                beta: beta.parse().map_err(|e| parse_err(&e))?,
+            }),
+            ["ba", m] => Ok(TopologyKind::BarabasiAlbert {
+                m: m.parse().map_err(|e| parse_err(&e))?,
+            }),
+            ["kademlia", bucket_size] => Ok(TopologyKind::Kademlia {
+                bucket_size: bucket_size.parse().map_err(|e| parse_err(&e))?,
+                selection: KadSelection::Uniform,
+            }),
+            ["kademlia", bucket_size, selection] => Ok(TopologyKind::Kademlia {
+                bucket_size: bucket_size.parse().map_err(|e| parse_err(&e))?,
+                selection: selection.parse()?,
+            }),
+            _ => Err(format!(
+                "unknown topology `{}`, expect complete, regular:<degree>, er:<p>, ws:<k>:<beta>, ba:<m> or kademlia:<bucket_size>[:<selection>]",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for TopologyKind {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for TopologyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologyKind::Complete => write!(f, "complete"),
+            TopologyKind::RandomRegular { degree } => write!(f, "regular:{}", degree),
+            TopologyKind::ErdosRenyi { p } => write!(f, "er:{}", p),
+            TopologyKind::WattsStrogatz { k, beta } => write!(f, "ws:{}:{}", k, beta),
+            TopologyKind::BarabasiAlbert { m } => write!(f, "ba:{}", m),
+            TopologyKind::Kademlia {
+                bucket_size,
+                selection,
+            } => write!(f, "kademlia:{}:{}", bucket_size, selection),
+        }
+    }
+}
+
+/// fully connected overlay.
+#[derive(Debug)]
+pub struct CompleteTopology {
+    node_size: usize,
+}
+
+impl Topology for CompleteTopology {
+    fn node_size(&self) -> usize {
+        self.node_size
+    }
+
+    fn neighbours(&self, node: NodeId) -> Vec<NodeId> {
+        (0..self.node_size).filter(|n| *n != node).collect()
+    }
+
+    fn degree(&self, _node: NodeId) -> usize {
+        self.node_size - 1
+    }
+}
+
+/// undirected graph stored as adjacency lists.
+#[derive(Debug)]
+pub struct GraphTopology {
+    adjacency: Vec<Vec<NodeId>>,
+}
+
+impl GraphTopology {
+    fn from_sets(sets: Vec<HashSet<NodeId>>) -> GraphTopology {
+        GraphTopology {
+            adjacency: sets
+                .into_iter()
+                .map(|set| {
+                    let mut list: Vec<NodeId> = set.into_iter().collect();
+                    list.sort_unstable();
+                    list
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Topology for GraphTopology {
+    fn node_size(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    fn neighbours(&self, node: NodeId) -> Vec<NodeId> {
+        self.adjacency[node].clone()
+    }
+
+    fn degree(&self, node: NodeId) -> usize {
+        self.adjacency[node].len()
+    }
+
+    fn random_neighbours(&self, node: NodeId, max_num: usize, rng: &mut SimRng) -> Vec<NodeId> {
+        self.adjacency[node]
+            .choose_multiple(rng, max_num)
+            .cloned()
+            .collect()
+    }
+}
+
+fn link(sets: &mut [HashSet<NodeId>], a: NodeId, b: NodeId) {
+    sets[a].insert(b);
+    sets[b].insert(a);
+}
+
+/// random `degree`-regular graph. Dense ones are the complement of a sparse one, which pairs
+/// up far more easily.
+fn random_regular(
+    node_size: usize,
+    degree: usize,
+    rng: &mut SimRng,
+) -> Result<GraphTopology, String> {
+    if degree <= node_size / 2 {
+        return Ok(GraphTopology::from_sets(pair_stubs(
+            node_size, degree, rng,
+        )?));
+    }
+    let sparse = pair_stubs(node_size, node_size - 1 - degree, rng)?;
+    let sets = (0..node_size)
+        .map(|a| {
+            (0..node_size)
+                .filter(|b| *b != a && !sparse[a].contains(b))
+                .collect()
+        })
+        .collect();
+    Ok(GraphTopology::from_sets(sets))
+}
+
+/// pairing model with restarts: repeatedly join two random free stubs that do not form a
+/// self loop or a duplicated link, start over when stuck, give up after `MAX_RESTART` tries.
+#[allow(clippy::manual_repeat_n)]
+fn pair_stubs(
+    node_size: usize,
+    degree: usize,
+    rng: &mut SimRng,
+) -> Result<Vec<HashSet<NodeId>>, String> {
+    const MAX_PICK_TRY: usize = 100;
+    const MAX_RESTART: usize = 100;
+    'restart: for _ in 0..MAX_RESTART {
+        let mut sets = vec![HashSet::new(); node_size];
+        let mut stubs: Vec<NodeId> = (0..node_size)
+            .flat_map(|n| std::iter::repeat(n).take(degree))
+            .collect();
+        while !stubs.is_empty() {
+            let mut linked = false;
+            for _ in 0..MAX_PICK_TRY {
+                let i = rng.gen_range(0..stubs.len());
+                let j = rng.gen_range(0..stubs.len());
+                let (a, b) = (stubs[i], stubs[j]);
+                if i == j || a == b || sets[a].contains(&b) {
+                    continue;
+                }
+                link(&mut sets, a, b);
+                stubs.swap_remove(i.max(j));
+                stubs.swap_remove(i.min(j));
+                linked = true;
+                break;
+            }
+            if !linked {
+                continue 'restart;
+            }
+        }
+        return Ok(sets);
+    }
+    Err(format!(
+        "no random {}-regular graph of {} nodes after {} tries",
+        degree, node_size, MAX_RESTART
+    ))
+}
+
+fn erdos_renyi(node_size: usize, p: f64, rng: &mut SimRng) -> GraphTopology {
+    let mut sets = vec![HashSet::new(); node_size];
+    for a in 0..node_size {
+        for b in (a + 1)..node_size {
+            if rng.gen_bool(p) {
+                link(&mut sets, a, b);
+            }
+        }
+    }
+    GraphTopology::from_sets(sets)
+}
+
+fn watts_strogatz(node_size: usize, k: usize, beta: f64, rng: &mut SimRng) -> GraphTopology {
+    let mut sets = vec![HashSet::new(); node_size];
+    for a in 0..node_size {
+        for j in 1..=k / 2 {
+            link(&mut sets, a, (a + j) % node_size);
+        }
+    }
+    // rewire each lattice link (a, a + j) to a random node with probability `beta`
+    for j in 1..=k / 2 {
+        for a in 0..node_size {
+            let b = (a + j) % node_size;
+            if !sets[a].contains(&b) || !rng.gen_bool(beta) {
+                continue;
+            }
+            if sets[a].len() >= node_size - 1 {
+                continue;
+            }
+            let c = loop {
+                let c = rng.gen_range(0..node_size);
+                if c != a && !sets[a].contains(&c) {
+                    break c;
+                }
+            };
+            sets[a].remove(&b);
+            sets[b].remove(&a);
+            link(&mut sets, a, c);
+        }
+    }
+    GraphTopology::from_sets(sets)
+}
+
+fn barabasi_albert(node_size: usize, m: usize, rng: &mut SimRng) -> GraphTopology {
+    let mut sets = vec![HashSet::new(); node_size];
+    // each node appears once per link end, so a uniform pick is degree proportional
+    let mut link_ends: Vec<NodeId> = Vec::new();
+    let initial = (m + 1).min(node_size);
+    for a in 0..initial {
+        for b in (a + 1)..initial {
+            link(&mut sets, a, b);
+            link_ends.push(a);
+            link_ends.push(b);
+        }
+    }
+    for new_node in initial..node_size {
+        // ordered, so linking order (and so later picks) doesn't depend on hasher state
+        let mut targets = BTreeSet::new();
+        while targets.len() < m {
+            targets.insert(*link_ends.choose(rng).unwrap());
+        }
+        for target in targets {
+            link(&mut sets, new_node, target);
+            link_ends.push(new_node);
+            link_ends.push(target);
+        }
+    }
+    GraphTopology::from_sets(sets)
+}
+
+#[cfg(test)]
+fn assert_undirected(topology: &dyn Topology) {
+    for a in 0..topology.node_size() {
+        for b in topology.neighbours(a) {
+            assert_ne!(a, b);
+            assert!(topology.neighbours(b).contains(&a));
+        }
+    }
+}
+
+#[test]
+fn test_get_random_neighbour() {
+    let topology = TopologyKind::Complete
+        .build(30, &mut crate::sim_rng::new_rng(0))
+        .unwrap();
+    let mut rng = crate::sim_rng::new_rng(0);
+    let r1 = topology.random_neighbours(0, 3, &mut rng);
+    let r2 = topology.random_neighbours(0, 3, &mut rng);
+    let r3 = topology.random_neighbours(0, 3, &mut rng);
+    assert_ne!(r1, r2);
+    assert_ne!(r1, r3);
+    assert_ne!(r2, r3);
+    assert_eq!(r1.len(), 3);
+    assert!(!r1.contains(&0));
+    assert_eq!(topology.degree(0), 29);
+
+    let mut replay_rng = crate::sim_rng::new_rng(0);
+    assert_eq!(r1, topology.random_neighbours(0, 3, &mut replay_rng));
+}
+
+#[test]
+fn test_build_topologies() {
+    let mut rng = crate::sim_rng::new_rng(0);
+
+    let regular = TopologyKind::RandomRegular { degree: 8 }
+        .build(100, &mut rng)
+        .unwrap();
+    assert_undirected(regular.as_ref());
+    assert!((0..100).all(|n| regular.degree(n) == 8));
+    assert_eq!(regular.random_neighbours(5, 20, &mut rng).len(), 8);
+    let dense = TopologyKind::RandomRegular { degree: 190 }
+        .build(200, &mut rng)
+        .unwrap();
+    assert_undirected(dense.as_ref());
+    assert!((0..200).all(|n| dense.degree(n) == 190));
+
+    let er = TopologyKind::ErdosRenyi { p: 0.1 }
+        .build(200, &mut rng)
+        .unwrap();
+    assert_undirected(er.as_ref());
+    let avg_degree = (0..200).map(|n| er.degree(n)).sum::<usize>() as f64 / 200.0;
+    assert!((15.0..25.0).contains(&avg_degree));
+
+    let ws = TopologyKind::WattsStrogatz { k: 6, beta: 0.1 }
+        .build(100, &mut rng)
+        .unwrap();
+    assert_undirected(ws.as_ref());
+    assert_eq!((0..100).map(|n| ws.degree(n)).sum::<usize>(), 600);
+
+    let ba = TopologyKind::BarabasiAlbert { m: 3 }
+        .build(100, &mut rng)
+        .unwrap();
+    assert_undirected(ba.as_ref());
+    assert!((0..100).all(|n| ba.degree(n) >= 3));
+    assert_eq!(
+        (0..100).map(|n| ba.degree(n)).sum::<usize>(),
+        2 * (6 + 96 * 3)
+    );
+}
+
+#[test]
+fn test_parse_topology_kind() {
+    for s in [
+        "complete",
+        "regular:8",
+        "er:0.05",
+        "ws:6:0.1",
+        "ba:3",
+        "kademlia:20:uniform",
+        "kademlia:8:per-bucket",
+    ] {
+        assert_eq!(s.parse::<TopologyKind>().unwrap().to_string(), s);
+    }
+    assert_eq!(
+        "kademlia:20".parse::<TopologyKind>(),
+        Ok(TopologyKind::Kademlia {
+            bucket_size: 20,
+            selection: KadSelection::Uniform
+        })
+    );
+    assert!("ws:6".parse::<TopologyKind>().is_err());
+    assert!("kademlia:8:nearest".parse::<TopologyKind>().is_err());
+    assert!("star".parse::<TopologyKind>().is_err());
+    assert!(TopologyKind::RandomRegular { degree: 3 }
+        .validate(101)
+        .is_err());
+    assert!(TopologyKind::WattsStrogatz { k: 5, beta: 0.1 }
+        .validate(100)
+        .is_err());
+}
@@ -0,0 +1,108 @@
+use crate::message::{counter_of, Message, MessageId, WireBytes};
+
+/// Bytes put on the wire while spreading the messages in flight, in total and per node (the
+/// load each node carries), both apart for each message.
+#[derive(Debug, Clone)]
+pub struct TrafficStats {
+    node_size: usize,
+    total: Vec<WireBytes>,
+    sent_bytes: Vec<Vec<u64>>,
+    recv_bytes: Vec<Vec<u64>>,
+}
+
+// getter
+impl TrafficStats {
+    pub fn total(&self, message_id: MessageId) -> WireBytes {
+        self.total.get(message_id).copied().unwrap_or_default()
+    }
+    /// bytes each node sent of the message of `message_id`.
+    pub fn sent_bytes(&self, message_id: MessageId) -> Vec<u64> {
+        self.node_bytes(&self.sent_bytes, message_id)
+    }
+    /// bytes each node recvd of the message of `message_id`.
+    pub fn recv_bytes(&self, message_id: MessageId) -> Vec<u64> {
+        self.node_bytes(&self.recv_bytes, message_id)
+    }
+}
+
+impl TrafficStats {
+    pub fn new(node_size: usize) -> TrafficStats {
+        TrafficStats {
+            node_size,
+            total: Vec::new(),
+            sent_bytes: Vec::new(),
+            recv_bytes: Vec::new(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.total.clear();
+        self.sent_bytes.clear();
+        self.recv_bytes.clear();
+    }
+
+    pub fn record_send(&mut self, message: &Message, bytes: WireBytes) {
+        *counter_of(&mut self.total, message.message_id) += bytes;
+        let sent = counter_of(&mut self.sent_bytes, message.message_id);
+        sent.resize(self.node_size, 0);
+        sent[message.from] += bytes.total();
+    }
+
+    /// only for messages not lost on the way.
+    pub fn record_recv(&mut self, message: &Message, bytes: WireBytes) {
+        let recv = counter_of(&mut self.recv_bytes, message.message_id);
+        recv.resize(self.node_size, 0);
+        recv[message.to] += bytes.total();
+    }
+
+    fn node_bytes(&self, counters: &[Vec<u64>], message_id: MessageId) -> Vec<u64> {
+        match counters.get(message_id) {
+            Some(bytes) if !bytes.is_empty() => bytes.clone(),
+            _ => vec![0; self.node_size],
+        }
+    }
+}
+
+#[test]
+fn test_traffic_stats() {
+    use crate::message::{HASH_SIZE, HEADER_SIZE, NODE_ID_SIZE};
+
+    let mut traffic = TrafficStats::new(3);
+    let mut full = Message::build_send_full_message(0, 1, 1);
+    full.add_bloomstatus(vec![1, 2]);
+    traffic.record_send(&full, full.wire_bytes(100));
+    traffic.record_recv(&full, full.wire_bytes(100));
+    // lost on the way
+    let hash = Message::build_send_hash_message(1, 2, 2);
+    traffic.record_send(&hash, hash.wire_bytes(100));
+
+    let total = traffic.total(0);
+    assert_eq!(total.payload, 100);
+    assert_eq!(total.hash, HASH_SIZE as u64);
+    assert_eq!(total.bloomstatus, 2 * NODE_ID_SIZE as u64);
+    assert_eq!(total.header, 2 * HEADER_SIZE as u64);
+    assert_eq!(
+        traffic.sent_bytes(0),
+        vec![full.wire_size(100) as u64, hash.wire_size(100) as u64, 0]
+    );
+    assert_eq!(
+        traffic.recv_bytes(0),
+        vec![0, full.wire_size(100) as u64, 0]
+    );
+    assert_eq!(traffic.sent_bytes(0).iter().sum::<u64>(), total.total());
+
+    // another message in flight keeps its own counters
+    let mut other = Message::build_send_hash_message(2, 0, 1);
+    other.message_id = 1;
+    traffic.record_send(&other, other.wire_bytes(100));
+    assert_eq!(
+        traffic.sent_bytes(1),
+        vec![0, 0, other.wire_size(100) as u64]
+    );
+    assert_eq!(traffic.sent_bytes(0)[2], 0);
+    assert_eq!(traffic.recv_bytes(1), vec![0, 0, 0]);
+
+    traffic.reset();
+    assert_eq!(traffic.total(0).total(), 0);
+    assert_eq!(traffic.sent_bytes(0), vec![0, 0, 0]);
+}